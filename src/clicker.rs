@@ -1,42 +1,75 @@
-use std::sync::atomic::{AtomicBool, AtomicUsize, AtomicU32, Ordering};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
-use rand::Rng;
+use std::time::{Duration, Instant};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use crate::input::{
+    cursor_position, find_window_origin, move_pointer,
+    double_click_time, get_active_window_title, parse_key_combo, send_click, hold_button, hold_keys, send_keys, send_scroll, send_text,
+    Key, MouseButton, ScrollDirection,
+};
+use crate::script::{Script, EXAMPLE_SCRIPT};
+use crate::macros::{first_move, Interpreter, MacroStep, Value, RecordedInput, Recorder, MAX_CALL_DEPTH};
 
 #[cfg(target_os = "windows")]
 use winapi::um::winuser::*;
 #[cfg(target_os = "windows")]
 use winapi::shared::minwindef::*;
 #[cfg(target_os = "windows")]
-use std::ffi::OsString;
-#[cfg(target_os = "windows")]
-use std::os::windows::ffi::OsStringExt;
-#[cfg(target_os = "windows")]
 use std::mem::zeroed;
 
 #[cfg(target_os = "linux")]
 use x11::xlib;
 #[cfg(target_os = "linux")]
-use libxdo_sys::{xdo_new, xdo_mouse_down, xdo_mouse_up, xdo_free};
-#[cfg(target_os = "linux")]
-use std::ffi::CString;
-#[cfg(target_os = "linux")]
 use x11::xlib::{XEvent, KeyPress, KeyRelease};
+#[cfg(target_os = "linux")]
+use std::ffi::{CStr, CString};
 
 #[derive(PartialEq, Clone, Debug)]
 pub enum ClickMode {
     Left,
     Right,
     Both,
+    Alternate,
+    Pattern,
+    Scroll,
+    Keys,
+    Type,
+    List,
+    Grid,
+    Drag,
+    Macro,
+    Script,
 }
 
 impl ClickMode {
+    pub fn clicks(&self, button: MouseButton) -> bool {
+        match self {
+            ClickMode::Left => button == MouseButton::Left,
+            ClickMode::Right => button == MouseButton::Right,
+            ClickMode::Both => true,
+            ClickMode::Alternate | ClickMode::Pattern | ClickMode::Scroll | ClickMode::Keys | ClickMode::Type | ClickMode::List | ClickMode::Grid | ClickMode::Drag | ClickMode::Macro | ClickMode::Script => false,
+        }
+    }
+
     pub fn to_usize(&self) -> usize {
         match self {
             ClickMode::Left => 0,
             ClickMode::Right => 1,
             ClickMode::Both => 2,
+            ClickMode::Alternate => 3,
+            ClickMode::Pattern => 4,
+            ClickMode::Scroll => 5,
+            ClickMode::Keys => 6,
+            ClickMode::Type => 7,
+            ClickMode::List => 8,
+            ClickMode::Grid => 9,
+            ClickMode::Drag => 10,
+            ClickMode::Macro => 11,
+            ClickMode::Script => 12,
         }
     }
 
@@ -45,42 +78,474 @@ impl ClickMode {
             0 => ClickMode::Left,
             1 => ClickMode::Right,
             2 => ClickMode::Both,
+            3 => ClickMode::Alternate,
+            4 => ClickMode::Pattern,
+            5 => ClickMode::Scroll,
+            6 => ClickMode::Keys,
+            7 => ClickMode::Type,
+            8 => ClickMode::List,
+            9 => ClickMode::Grid,
+            10 => ClickMode::Drag,
+            11 => ClickMode::Macro,
+            12 => ClickMode::Script,
             _ => ClickMode::Left,
         }
     }
 }
 
+// One tick of a single-stream mode
+#[derive(PartialEq, Clone, Debug)]
+pub enum Step {
+    Click(MouseButton),
+    Rest,
+    Scroll(ScrollDirection, u32),
+    Keys(Vec<Key>),
+    Type,
+    Point(ClickPoint),
+    Macro,
+    Script,
+}
+
+// Parse a pattern like "LLR-M" into steps: L/R/M click that button, '-' rests for one slot
+pub fn parse_pattern(pattern: &str) -> Result<Vec<Step>, String> {
+    let mut steps = Vec::new();
+    for (position, c) in pattern.chars().enumerate() {
+        let step = match c.to_ascii_uppercase() {
+            'L' => Step::Click(MouseButton::Left),
+            'R' => Step::Click(MouseButton::Right),
+            'M' => Step::Click(MouseButton::Middle),
+            '-' => Step::Rest,
+            ' ' => continue,
+            _ => return Err(format!("Unexpected '{}' at position {}", c, position + 1)),
+        };
+        steps.push(step);
+    }
+
+    if steps.is_empty() {
+        return Err("Pattern is empty".to_string());
+    }
+    if !steps.iter().any(|step| matches!(step, Step::Click(_))) {
+        return Err("Pattern has no clicks".to_string());
+    }
+    Ok(steps)
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Activation {
+    Hold,
+    Count,
+    Latch,
+    AlwaysOn,
+}
+
+impl Activation {
+    pub fn to_usize(self) -> usize {
+        match self {
+            Activation::Hold => 0,
+            Activation::Count => 1,
+            Activation::Latch => 2,
+            Activation::AlwaysOn => 3,
+        }
+    }
+
+    pub fn from_usize(value: usize) -> Self {
+        match value {
+            1 => Activation::Count,
+            2 => Activation::Latch,
+            3 => Activation::AlwaysOn,
+            _ => Activation::Hold,
+        }
+    }
+}
+
+// One entry of the click list: where to click, with what, and how long to wait afterwards.
+// With a drag end the button is held from the point to there instead of clicked.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct ClickPoint {
+    pub x: i32,
+    pub y: i32,
+    pub button: MouseButton,
+    pub clicks: u32,
+    pub delay_ms: u32,
+    pub drag_to: Option<(i32, i32)>,
+}
+
+impl ClickPoint {
+    pub fn at(x: i32, y: i32) -> Self {
+        Self { x, y, button: MouseButton::Left, clicks: 1, delay_ms: 500, drag_to: None }
+    }
+}
+
+// Order the grid sweep visits its cells in
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum GridOrder {
+    Rows,
+    Columns,
+    Serpentine,
+    Shuffled,
+}
+
+impl GridOrder {
+    pub fn to_usize(self) -> usize {
+        match self {
+            GridOrder::Rows => 0,
+            GridOrder::Columns => 1,
+            GridOrder::Serpentine => 2,
+            GridOrder::Shuffled => 3,
+        }
+    }
+
+    pub fn from_usize(value: usize) -> Self {
+        match value {
+            1 => GridOrder::Columns,
+            2 => GridOrder::Serpentine,
+            3 => GridOrder::Shuffled,
+            _ => GridOrder::Rows,
+        }
+    }
+}
+
+// (row, column) of every cell of a grid in sweep order. Shuffled order comes from the seed so
+// one pass still visits every cell exactly once.
+fn grid_cells(rows: u32, columns: u32, order: GridOrder, seed: u64) -> Vec<(u32, u32)> {
+    let mut cells = Vec::new();
+    match order {
+        GridOrder::Columns => {
+            for column in 0..columns {
+                for row in 0..rows {
+                    cells.push((row, column));
+                }
+            }
+        }
+        _ => {
+            for row in 0..rows {
+                for column in 0..columns {
+                    // Serpentine runs every other row backwards
+                    let column = if order == GridOrder::Serpentine && row % 2 == 1 {
+                        columns - 1 - column
+                    } else {
+                        column
+                    };
+                    cells.push((row, column));
+                }
+            }
+        }
+    }
+
+    if order == GridOrder::Shuffled {
+        cells.shuffle(&mut StdRng::seed_from_u64(seed));
+    }
+    cells
+}
+
+// How the pointer travels to a click target
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum MotionPath {
+    Teleport,
+    Linear,
+    Eased,
+    Bezier,
+}
+
+impl MotionPath {
+    pub fn to_usize(self) -> usize {
+        match self {
+            MotionPath::Teleport => 0,
+            MotionPath::Linear => 1,
+            MotionPath::Eased => 2,
+            MotionPath::Bezier => 3,
+        }
+    }
+
+    pub fn from_usize(value: usize) -> Self {
+        match value {
+            1 => MotionPath::Linear,
+            2 => MotionPath::Eased,
+            3 => MotionPath::Bezier,
+            _ => MotionPath::Teleport,
+        }
+    }
+}
+
+// Ease-in-out cubic, slow at both ends like a hand moving a mouse
+fn ease(t: f64) -> f64 {
+    if t < 0.5 {
+        4.0 * t * t * t
+    } else {
+        1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+    }
+}
+
+fn cubic_bezier(p0: (f64, f64), p1: (f64, f64), p2: (f64, f64), p3: (f64, f64), t: f64) -> (f64, f64) {
+    let u = 1.0 - t;
+    let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+    (
+        a * p0.0 + b * p1.0 + c * p2.0 + d * p3.0,
+        a * p0.1 + b * p1.1 + c * p2.1 + d * p3.1,
+    )
+}
+
+// Where a click burst lands
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum ClickTarget {
+    Cursor,
+    Fixed,
+    Region,
+}
+
+impl ClickTarget {
+    pub fn to_usize(self) -> usize {
+        match self {
+            ClickTarget::Cursor => 0,
+            ClickTarget::Fixed => 1,
+            ClickTarget::Region => 2,
+        }
+    }
+
+    pub fn from_usize(value: usize) -> Self {
+        match value {
+            1 => ClickTarget::Fixed,
+            2 => ClickTarget::Region,
+            _ => ClickTarget::Cursor,
+        }
+    }
+}
+
+// What the auto-typer sends for a line break in the text
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Newline {
+    Enter,
+    ShiftEnter,
+    Skip,
+}
+
+impl Newline {
+    pub fn to_usize(self) -> usize {
+        match self {
+            Newline::Enter => 0,
+            Newline::ShiftEnter => 1,
+            Newline::Skip => 2,
+        }
+    }
+
+    pub fn from_usize(value: usize) -> Self {
+        match value {
+            1 => Newline::ShiftEnter,
+            2 => Newline::Skip,
+            _ => Newline::Enter,
+        }
+    }
+}
+
+// Held state and press counter of one physical trigger, a mouse button or a bound key
+#[derive(Clone, Default)]
+struct TriggerInput {
+    held: Arc<AtomicBool>,
+    presses: Arc<AtomicU32>,
+}
+
+impl TriggerInput {
+    // Safe to call repeatedly with the same state, only a release-to-press edge counts
+    fn set(&self, held: bool) {
+        let was_held = self.held.swap(held, Ordering::Relaxed);
+        if held && !was_held {
+            self.presses.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn held(&self) -> bool {
+        self.held.load(Ordering::Relaxed)
+    }
+
+    fn presses(&self) -> u32 {
+        self.presses.load(Ordering::Relaxed)
+    }
+}
+
+// Physical state of every mouse button that can act as a trigger
+#[derive(Clone, Default)]
+struct MouseInputs {
+    left: TriggerInput,
+    right: TriggerInput,
+    middle: TriggerInput,
+    x1: TriggerInput,
+    x2: TriggerInput,
+}
+
+impl MouseInputs {
+    fn get(&self, button: MouseButton) -> &TriggerInput {
+        match button {
+            MouseButton::Left => &self.left,
+            MouseButton::Right => &self.right,
+            MouseButton::Middle => &self.middle,
+            MouseButton::X1 => &self.x1,
+            MouseButton::X2 => &self.x2,
+        }
+    }
+}
+
+// Every field is a shared handle, so clones drive the same clicker from other threads
+#[derive(Clone)]
 pub struct AutoClicker {
-    min_cps: Arc<AtomicU32>,
-    max_cps: Arc<AtomicU32>,
+    left_min_cps: Arc<AtomicU32>,
+    left_max_cps: Arc<AtomicU32>,
+    right_min_cps: Arc<AtomicU32>,
+    right_max_cps: Arc<AtomicU32>,
     is_running: Arc<AtomicBool>,
     click_mode: Arc<AtomicUsize>,
-    left_pressed: Arc<AtomicBool>,
-    right_pressed: Arc<AtomicBool>,
-    window_title: String,
+    mouse: MouseInputs,
+    left_trigger_button: Arc<AtomicUsize>,
+    right_trigger_button: Arc<AtomicUsize>,
+    left_key: TriggerInput,
+    right_key: TriggerInput,
+    left_trigger_key: Arc<AtomicUsize>,
+    right_trigger_key: Arc<AtomicUsize>,
+    left_latched: Arc<AtomicBool>,
+    right_latched: Arc<AtomicBool>,
+    activation: Arc<AtomicUsize>,
+    activation_clicks: Arc<AtomicU32>,
+    clicks_fired: Arc<AtomicU32>,
+    pattern: Arc<Mutex<String>>,
+    key_combo: Arc<Mutex<String>>,
+    type_text: Arc<Mutex<String>>,
+    type_from_clipboard: Arc<AtomicBool>,
+    type_min_delay_ms: Arc<AtomicU32>,
+    type_max_delay_ms: Arc<AtomicU32>,
+    newline: Arc<AtomicUsize>,
+    scroll_direction: Arc<AtomicUsize>,
+    scroll_notches: Arc<AtomicU32>,
+    burst_clicks: Arc<AtomicU32>,
+    burst_gap_ms: Arc<AtomicU32>,
+    click_modifiers: Arc<Mutex<Vec<Key>>>,
+    click_target: Arc<AtomicUsize>,
+    target_x: Arc<AtomicI32>,
+    target_y: Arc<AtomicI32>,
+    restore_pointer: Arc<AtomicBool>,
+    click_points: Arc<Mutex<Vec<ClickPoint>>>,
+    list_loops: Arc<AtomicU32>,
+    anchor_window: Arc<Mutex<String>>,
+    region: Arc<Mutex<(i32, i32, i32, i32)>>,
+    region_min_distance: Arc<AtomicU32>,
+    last_region_point: Arc<Mutex<Option<(i32, i32)>>>,
+    grid_rows: Arc<AtomicU32>,
+    grid_columns: Arc<AtomicU32>,
+    grid_order: Arc<AtomicUsize>,
+    grid_delay_ms: Arc<AtomicU32>,
+    grid_seed: Arc<AtomicU32>,
+    motion_path: Arc<AtomicUsize>,
+    motion_duration_ms: Arc<AtomicU32>,
+    motion_rate: Arc<AtomicU32>,
+    drag_from: Arc<Mutex<(i32, i32)>>,
+    drag_to: Arc<Mutex<(i32, i32)>>,
+    drag_delay_ms: Arc<AtomicU32>,
+    recording: Arc<AtomicBool>,
+    recorder: Arc<Mutex<Recorder>>,
+    macro_steps: Arc<Mutex<Vec<MacroStep>>>,
+    script_source: Arc<Mutex<String>>,
+    script_version: Arc<AtomicUsize>,
+    script_error: Arc<Mutex<String>>,
+    script_log: Arc<Mutex<Vec<String>>>,
+    macro_speed: Arc<AtomicU32>,
+    macro_loops: Arc<AtomicU32>,
+    macro_relative: Arc<AtomicBool>,
+    macro_step: Arc<AtomicUsize>,
+    macro_loop: Arc<AtomicU32>,
+    macro_start: Arc<AtomicUsize>,
+    macro_once: Arc<AtomicBool>,
+    macro_stepping: Arc<AtomicBool>,
+    macro_advance: Arc<AtomicBool>,
+    macro_next_step: Arc<Mutex<String>>,
+    macro_vars: Arc<Mutex<Vec<(String, i64)>>>,
+    // Titles of the app's own windows, which are never clicked into
+    window_titles: Vec<String>,
 }
 
 impl Default for AutoClicker {
     fn default() -> Self {
         Self {
-            min_cps: Arc::new(AtomicU32::new(5)),
-            max_cps: Arc::new(AtomicU32::new(25)),
+            left_min_cps: Arc::new(AtomicU32::new(5)),
+            left_max_cps: Arc::new(AtomicU32::new(25)),
+            right_min_cps: Arc::new(AtomicU32::new(5)),
+            right_max_cps: Arc::new(AtomicU32::new(25)),
             is_running: Arc::new(AtomicBool::new(false)),
             click_mode: Arc::new(AtomicUsize::new(0)),
-            left_pressed: Arc::new(AtomicBool::new(false)),
-            right_pressed: Arc::new(AtomicBool::new(false)),
-            window_title: "Auto Clicker".to_string(),
+            mouse: MouseInputs::default(),
+            left_trigger_button: Arc::new(AtomicUsize::new(MouseButton::Left.to_usize())),
+            right_trigger_button: Arc::new(AtomicUsize::new(MouseButton::Right.to_usize())),
+            left_key: TriggerInput::default(),
+            right_key: TriggerInput::default(),
+            left_trigger_key: Arc::new(AtomicUsize::new(0)),
+            right_trigger_key: Arc::new(AtomicUsize::new(0)),
+            left_latched: Arc::new(AtomicBool::new(false)),
+            right_latched: Arc::new(AtomicBool::new(false)),
+            activation: Arc::new(AtomicUsize::new(Activation::Hold.to_usize())),
+            activation_clicks: Arc::new(AtomicU32::new(10)),
+            clicks_fired: Arc::new(AtomicU32::new(0)),
+            pattern: Arc::new(Mutex::new("LR".to_string())),
+            key_combo: Arc::new(Mutex::new("Space".to_string())),
+            type_text: Arc::new(Mutex::new(String::new())),
+            type_from_clipboard: Arc::new(AtomicBool::new(false)),
+            type_min_delay_ms: Arc::new(AtomicU32::new(30)),
+            type_max_delay_ms: Arc::new(AtomicU32::new(80)),
+            newline: Arc::new(AtomicUsize::new(Newline::Enter.to_usize())),
+            scroll_direction: Arc::new(AtomicUsize::new(ScrollDirection::Down.to_usize())),
+            scroll_notches: Arc::new(AtomicU32::new(1)),
+            burst_clicks: Arc::new(AtomicU32::new(1)),
+            burst_gap_ms: Arc::new(AtomicU32::new(50)),
+            click_modifiers: Arc::new(Mutex::new(Vec::new())),
+            click_target: Arc::new(AtomicUsize::new(ClickTarget::Cursor.to_usize())),
+            target_x: Arc::new(AtomicI32::new(0)),
+            target_y: Arc::new(AtomicI32::new(0)),
+            restore_pointer: Arc::new(AtomicBool::new(true)),
+            click_points: Arc::new(Mutex::new(Vec::new())),
+            list_loops: Arc::new(AtomicU32::new(0)),
+            anchor_window: Arc::new(Mutex::new(String::new())),
+            region: Arc::new(Mutex::new((0, 0, 200, 200))),
+            region_min_distance: Arc::new(AtomicU32::new(0)),
+            last_region_point: Arc::new(Mutex::new(None)),
+            grid_rows: Arc::new(AtomicU32::new(3)),
+            grid_columns: Arc::new(AtomicU32::new(3)),
+            grid_order: Arc::new(AtomicUsize::new(GridOrder::Rows.to_usize())),
+            grid_delay_ms: Arc::new(AtomicU32::new(200)),
+            grid_seed: Arc::new(AtomicU32::new(0)),
+            motion_path: Arc::new(AtomicUsize::new(MotionPath::Teleport.to_usize())),
+            motion_duration_ms: Arc::new(AtomicU32::new(200)),
+            motion_rate: Arc::new(AtomicU32::new(60)),
+            drag_from: Arc::new(Mutex::new((0, 0))),
+            drag_to: Arc::new(Mutex::new((100, 0))),
+            drag_delay_ms: Arc::new(AtomicU32::new(500)),
+            recording: Arc::new(AtomicBool::new(false)),
+            recorder: Arc::new(Mutex::new(Recorder::default())),
+            macro_steps: Arc::new(Mutex::new(Vec::new())),
+            script_source: Arc::new(Mutex::new(EXAMPLE_SCRIPT.to_string())),
+            script_version: Arc::new(AtomicUsize::new(0)),
+            script_error: Arc::new(Mutex::new(String::new())),
+            script_log: Arc::new(Mutex::new(Vec::new())),
+            macro_speed: Arc::new(AtomicU32::new(100)),
+            macro_loops: Arc::new(AtomicU32::new(1)),
+            macro_relative: Arc::new(AtomicBool::new(false)),
+            macro_step: Arc::new(AtomicUsize::new(0)),
+            macro_loop: Arc::new(AtomicU32::new(0)),
+            macro_start: Arc::new(AtomicUsize::new(0)),
+            macro_once: Arc::new(AtomicBool::new(false)),
+            macro_stepping: Arc::new(AtomicBool::new(false)),
+            macro_advance: Arc::new(AtomicBool::new(false)),
+            macro_next_step: Arc::new(Mutex::new(String::new())),
+            macro_vars: Arc::new(Mutex::new(Vec::new())),
+            window_titles: ["Auto Clicker", "Macro editor", "Draw region"].map(String::from).to_vec(),
         }
     }
 }
 
 // Global state for the hooks
 #[cfg(target_os = "windows")]
-static mut LEFT_PRESSED: Option<Arc<AtomicBool>> = None;
+static mut MOUSE_INPUTS: Option<MouseInputs> = None;
 #[cfg(target_os = "windows")]
-static mut RIGHT_PRESSED: Option<Arc<AtomicBool>> = None;
+static mut KEY_TRIGGERS: Vec<(Arc<AtomicUsize>, TriggerInput)> = Vec::new();
 #[cfg(target_os = "windows")]
 static mut IS_RUNNING: Option<Arc<AtomicBool>> = None;
+#[cfg(target_os = "windows")]
+static mut CLICKER: Option<AutoClicker> = None;
 
 #[cfg(target_os = "windows")]
 unsafe extern "system" fn mouse_hook_proc(n_code: i32, w_param: WPARAM, l_param: LPARAM) -> LRESULT {
@@ -91,22 +556,50 @@ unsafe extern "system" fn mouse_hook_proc(n_code: i32, w_param: WPARAM, l_param:
         let is_injected = (mouse_hook_struct.flags & LLMHF_INJECTED) != 0;
         
         if !is_injected {
-            if let Some(left_pressed) = LEFT_PRESSED.as_ref() {
-                if let Some(right_pressed) = RIGHT_PRESSED.as_ref() {
-                    match w_param as u32 {
-                        WM_LBUTTONDOWN | WM_NCLBUTTONDOWN => {
-                            left_pressed.store(true, Ordering::Relaxed);
-                        }
-                        WM_LBUTTONUP | WM_NCLBUTTONUP => {
-                            left_pressed.store(false, Ordering::Relaxed);
-                        }
-                        WM_RBUTTONDOWN | WM_NCRBUTTONDOWN => {
-                            right_pressed.store(true, Ordering::Relaxed);
+            // X buttons say which one they are in the high word of mouseData
+            let x_button = if (mouse_hook_struct.mouseData >> 16) as u16 == XBUTTON2 {
+                MouseButton::X2
+            } else {
+                MouseButton::X1
+            };
+
+            let event = match w_param as u32 {
+                WM_LBUTTONDOWN | WM_NCLBUTTONDOWN => Some((MouseButton::Left, true)),
+                WM_LBUTTONUP | WM_NCLBUTTONUP => Some((MouseButton::Left, false)),
+                WM_RBUTTONDOWN | WM_NCRBUTTONDOWN => Some((MouseButton::Right, true)),
+                WM_RBUTTONUP | WM_NCRBUTTONUP => Some((MouseButton::Right, false)),
+                WM_MBUTTONDOWN | WM_NCMBUTTONDOWN => Some((MouseButton::Middle, true)),
+                WM_MBUTTONUP | WM_NCMBUTTONUP => Some((MouseButton::Middle, false)),
+                WM_XBUTTONDOWN | WM_NCXBUTTONDOWN => Some((x_button, true)),
+                WM_XBUTTONUP | WM_NCXBUTTONUP => Some((x_button, false)),
+                _ => None,
+            };
+
+            if let (Some((button, down)), Some(mouse_inputs)) = (event, MOUSE_INPUTS.as_ref()) {
+                mouse_inputs.get(button).set(down);
+            }
+
+            if let Some(clicker) = CLICKER.as_ref().filter(|clicker| clicker.is_recording()) {
+                // Wheel deltas are in the high word, one notch is WHEEL_DELTA
+                let delta = (mouse_hook_struct.mouseData >> 16) as u16 as i16;
+                let notches = (delta.unsigned_abs() / WHEEL_DELTA as u16).max(1);
+                match w_param as u32 {
+                    WM_MOUSEMOVE => clicker.record(RecordedInput::Move(mouse_hook_struct.pt.x, mouse_hook_struct.pt.y)),
+                    WM_MOUSEWHEEL | WM_MOUSEHWHEEL => {
+                        let direction = match (w_param as u32 == WM_MOUSEHWHEEL, delta > 0) {
+                            (false, true) => ScrollDirection::Up,
+                            (false, false) => ScrollDirection::Down,
+                            (true, true) => ScrollDirection::Right,
+                            (true, false) => ScrollDirection::Left,
+                        };
+                        for _ in 0..notches {
+                            clicker.record(RecordedInput::Scroll(direction));
                         }
-                        WM_RBUTTONUP | WM_NCRBUTTONUP => {
-                            right_pressed.store(false, Ordering::Relaxed);
+                    }
+                    _ => {
+                        if let Some((button, down)) = event {
+                            clicker.record(RecordedInput::Button(button, down));
                         }
-                        _ => {}
                     }
                 }
             }
@@ -123,6 +616,40 @@ unsafe extern "system" fn keyboard_hook_proc(n_code: i32, w_param: WPARAM, l_par
         let keyboard_hook_struct = *(l_param as *const KBDLLHOOKSTRUCT);
         let vk_code = keyboard_hook_struct.vkCode;
 
+        if (keyboard_hook_struct.flags & LLKHF_INJECTED) == 0 {
+            // Real key presses go into the recording, if one is running
+            if let (Some(clicker), Some(key)) = (CLICKER.as_ref(), Key::from_vk(vk_code as u16)) {
+                match w_param as u32 {
+                    WM_KEYDOWN | WM_SYSKEYDOWN => clicker.record(RecordedInput::Key(key, true)),
+                    WM_KEYUP | WM_SYSKEYUP => clicker.record(RecordedInput::Key(key, false)),
+                    _ => {}
+                }
+            }
+
+            // Keys bound as click triggers, ignoring our own injected key events
+            for (trigger_key, input) in KEY_TRIGGERS.iter() {
+                if let Some(key) = Key::from_usize(trigger_key.load(Ordering::Relaxed)) {
+                    if key.vk() as u32 == vk_code {
+                        match w_param as u32 {
+                            WM_KEYDOWN | WM_SYSKEYDOWN => input.set(true),
+                            WM_KEYUP | WM_SYSKEYUP => input.set(false),
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }
+
+        // F7 captures the pointer position into the click list, F8 starts and stops recording
+        if w_param as u32 == WM_KEYUP {
+            if let Some(clicker) = CLICKER.as_ref() {
+                match vk_code as i32 {
+                    VK_F7 => clicker.capture_point(),
+                    VK_F8 => clicker.toggle_recording(),
+                    _ => {}
+                }
+            }
+        }
 
         if vk_code == VK_F6 as u32 {
             match w_param as u32 {
@@ -152,69 +679,342 @@ unsafe extern "system" fn keyboard_hook_proc(n_code: i32, w_param: WPARAM, l_par
     CallNextHookEx(std::ptr::null_mut(), n_code, w_param, l_param)
 }
 
+// Pick a random CPS in the range and turn it into the length of one click cycle
+fn click_cycle(rng: &mut impl Rng, min_cps: u32, max_cps: u32) -> Duration {
+    let cps = rng.gen_range(min_cps..=max_cps.max(min_cps)).max(1);
+
+    // 1000ms / CPS gives us the total time per click cycle
+    Duration::from_millis((1000.0 / cps as f32) as u64)
+}
+
+// Sleep out whatever is left of the cycle once the click itself has been sent
+fn sleep_rest_of_cycle(rng: &mut impl Rng, min_cps: &AtomicU32, max_cps: &AtomicU32, started: Instant) {
+    let current_min = min_cps.load(Ordering::SeqCst);
+    let current_max = max_cps.load(Ordering::SeqCst);
+    let cycle = click_cycle(rng, current_min, current_max);
+    thread::sleep(cycle.saturating_sub(started.elapsed()));
+}
+
+// XInput2 raw events: the X1/X2 side buttons (X11 buttons 8 and 9) and, while recording, all user input
 #[cfg(target_os = "linux")]
-fn get_active_window_title() -> String {
-    unsafe {
+fn spawn_xi2_monitor(clicker: AutoClicker) {
+    use x11::xinput2::*;
+
+    thread::spawn(move || unsafe {
         let display = x11::xlib::XOpenDisplay(std::ptr::null());
         if display.is_null() {
-            return String::new();
+            println!("Failed to open X11 display");
+            return;
         }
 
-        let mut window: x11::xlib::Window = 0;
-        let mut revert: i32 = 0;
-        x11::xlib::XGetInputFocus(display, &mut window, &mut revert);
-        
-        let mut name: *mut i8 = std::ptr::null_mut();
-        x11::xlib::XFetchName(display, window, &mut name);
-        
-        x11::xlib::XCloseDisplay(display);
-        
-        if !name.is_null() {
-            let title = CString::from_raw(name).into_string().unwrap_or_default();
-            title
-        } else {
-            String::new()
+        let mut opcode = 0;
+        let mut first_event = 0;
+        let mut first_error = 0;
+        let extension = CString::new("XInputExtension").unwrap();
+        if x11::xlib::XQueryExtension(display, extension.as_ptr(), &mut opcode, &mut first_event, &mut first_error) == 0 {
+            println!("XInput2 is not available, side buttons and macro recording won't work");
+            return;
+        }
+
+        // Our own clicks come from the XTEST devices, don't mistake them for the user
+        let mut xtest_devices = Vec::new();
+        let mut device_count = 0;
+        let devices = XIQueryDevice(display, XIAllDevices, &mut device_count);
+        if !devices.is_null() {
+            for i in 0..device_count as usize {
+                let device = &*devices.add(i);
+                if CStr::from_ptr(device.name).to_string_lossy().contains("XTEST") {
+                    xtest_devices.push(device.deviceid);
+                }
+            }
+            XIFreeDeviceInfo(devices);
+        }
+
+        let root = x11::xlib::XDefaultRootWindow(display);
+        let mut mask = [0u8; (XI_LASTEVENT as usize >> 3) + 1];
+        XISetMask(&mut mask, XI_RawButtonPress);
+        XISetMask(&mut mask, XI_RawButtonRelease);
+        XISetMask(&mut mask, XI_RawMotion);
+        XISetMask(&mut mask, XI_RawKeyPress);
+        XISetMask(&mut mask, XI_RawKeyRelease);
+        let mut event_mask = XIEventMask {
+            deviceid: XIAllMasterDevices,
+            mask_len: mask.len() as i32,
+            mask: mask.as_mut_ptr(),
+        };
+        XISelectEvents(display, root, &mut event_mask, 1);
+
+        let mut event: x11::xlib::XEvent = std::mem::zeroed();
+        loop {
+            x11::xlib::XNextEvent(display, &mut event);
+            let mut cookie = event.generic_event_cookie;
+            if cookie.type_ != x11::xlib::GenericEvent || cookie.extension != opcode {
+                continue;
+            }
+            if x11::xlib::XGetEventData(display, &mut cookie) == 0 {
+                continue;
+            }
+
+            let raw = &*(cookie.data as *const XIRawEvent);
+            if !xtest_devices.contains(&raw.sourceid) {
+                let evtype = cookie.evtype;
+                let pressed = evtype == XI_RawButtonPress || evtype == XI_RawKeyPress;
+                if evtype == XI_RawButtonPress || evtype == XI_RawButtonRelease {
+                    let button = match raw.detail {
+                        1 => Some(MouseButton::Left),
+                        2 => Some(MouseButton::Middle),
+                        3 => Some(MouseButton::Right),
+                        8 => Some(MouseButton::X1),
+                        9 => Some(MouseButton::X2),
+                        _ => None,
+                    };
+                    let scroll = match raw.detail {
+                        4 => Some(ScrollDirection::Up),
+                        5 => Some(ScrollDirection::Down),
+                        6 => Some(ScrollDirection::Left),
+                        7 => Some(ScrollDirection::Right),
+                        _ => None,
+                    };
+
+                    // Side buttons aren't in the XQueryPointer mask, the others are polled
+                    if let Some(button @ (MouseButton::X1 | MouseButton::X2)) = button {
+                        clicker.mouse.get(button).set(pressed);
+                    }
+                    if let Some(button) = button {
+                        clicker.record(RecordedInput::Button(button, pressed));
+                    }
+                    if let (Some(direction), true) = (scroll, pressed) {
+                        clicker.record(RecordedInput::Scroll(direction));
+                    }
+                } else if evtype == XI_RawMotion && clicker.is_recording() {
+                    // Raw motion only has deltas, so ask for the position while recording
+                    let (mut root_return, mut child_return) = (0, 0);
+                    let (mut x, mut y, mut win_x, mut win_y) = (0, 0, 0, 0);
+                    let mut mask_return = 0;
+                    x11::xlib::XQueryPointer(
+                        display, root, &mut root_return, &mut child_return,
+                        &mut x, &mut y, &mut win_x, &mut win_y, &mut mask_return,
+                    );
+                    clicker.record(RecordedInput::Move(x, y));
+                } else if evtype == XI_RawKeyPress || evtype == XI_RawKeyRelease {
+                    let keysym = x11::xlib::XkbKeycodeToKeysym(display, raw.detail as u8, 0, 0);
+                    if let Some(key) = Key::from_keysym(keysym as u32) {
+                        clicker.record(RecordedInput::Key(key, pressed));
+                    }
+                }
+            }
+            x11::xlib::XFreeEventData(display, &mut cookie);
+        }
+    });
+}
+
+// Decides, for one click stream, whether the activation mode wants it clicking right now
+struct Trigger {
+    remaining: u32,
+    last_presses: u32,
+    was_enabled: bool,
+    latched: Arc<AtomicBool>,
+}
+
+impl Trigger {
+    // `latched` is where the Latch state is published for the GUI
+    fn new(latched: Arc<AtomicBool>) -> Self {
+        Self {
+            remaining: 0,
+            last_presses: 0,
+            was_enabled: false,
+            latched,
+        }
+    }
+
+    // `enabled` means the clicker is running in a mode that uses this stream, `pressed` and
+    // `presses` are the held state and press counter of the button(s) driving it
+    fn active(&mut self, clicker: &AutoClicker, enabled: bool, pressed: bool, presses: u32) -> bool {
+        let started = enabled && !self.was_enabled;
+        let stopped = !enabled && self.was_enabled;
+        self.was_enabled = enabled;
+        if !enabled {
+            // Only clear on the edge, streams that never run share the flag with ones that do
+            if stopped {
+                self.latched.store(false, Ordering::Relaxed);
+            }
+            self.remaining = 0;
+            self.last_presses = presses;
+            return false;
+        }
+
+        let new_press = presses != self.last_presses;
+        self.last_presses = presses;
+
+        match clicker.activation() {
+            Activation::Hold => pressed,
+            Activation::AlwaysOn => true,
+            Activation::Latch => {
+                // Each press flips the latch, the first starts clicking and the next stops it
+                if new_press {
+                    self.latched.fetch_xor(true, Ordering::Relaxed);
+                }
+                self.latched.load(Ordering::Relaxed)
+            }
+            Activation::Count => {
+                // Starting the clicker or a fresh press arms one batch, presses during a batch are ignored
+                if self.remaining == 0 && (started || new_press) {
+                    self.remaining = clicker.activation_clicks.load(Ordering::Relaxed).max(1);
+                    clicker.clicks_fired.store(0, Ordering::Relaxed);
+                }
+                self.remaining > 0
+            }
         }
     }
+
+    // Called after the stream sent something, so our own injected presses don't re-arm a batch
+    fn clicked(&mut self, clicker: &AutoClicker, presses: u32) {
+        self.remaining = self.remaining.saturating_sub(1);
+        self.last_presses = presses;
+        clicker.clicks_fired.fetch_add(1, Ordering::Relaxed);
+    }
 }
 
-#[cfg(target_os = "linux")]
-fn get_active_window() -> x11::xlib::Window {
-    unsafe {
-        let display = x11::xlib::XOpenDisplay(std::ptr::null());
-        if display.is_null() {
-            return 0;
+fn spawn_scheduler(button: MouseButton, clicker: AutoClicker) {
+    thread::spawn(move || {
+        let mut rng = rand::thread_rng();
+        let (min_cps, max_cps) = clicker.cps_range(button);
+        let latched = match button {
+            MouseButton::Right => &clicker.right_latched,
+            _ => &clicker.left_latched,
+        };
+        let mut trigger = Trigger::new(latched.clone());
+
+        loop {
+            let current_mode = ClickMode::from_usize(clicker.click_mode.load(Ordering::Relaxed));
+            let enabled = clicker.is_running() && current_mode.clicks(button);
+            if !trigger.active(&clicker, enabled, clicker.held(button), clicker.presses(button)) {
+                thread::sleep(Duration::from_millis(10));
+                continue;
+            }
+
+            // Check if foreground window is not our application
+            let started = Instant::now();
+            if !clicker.own_window_focused() {
+                clicker.click_unit(button);
+                trigger.clicked(&clicker, clicker.presses(button));
+            }
+
+            sleep_rest_of_cycle(&mut rng, min_cps, max_cps, started);
         }
+    });
+}
 
-        let mut window: x11::xlib::Window = 0;
-        let mut revert: i32 = 0;
-        x11::xlib::XGetInputFocus(display, &mut window, &mut revert);
-        
-        x11::xlib::XCloseDisplay(display);
-        window
+// Sleep in short slices so a long delay ends as soon as the clicker is stopped
+fn sleep_while_running(clicker: &AutoClicker, duration: Duration) {
+    let started = Instant::now();
+    while clicker.is_running() && started.elapsed() < duration {
+        thread::sleep((duration - started.elapsed()).min(Duration::from_millis(10)));
     }
 }
 
+// Runs every mode that isn't a plain left/right/both click stream, one step per tick while
+// either button activates them (macros only need the clicker running). They share the
+// primary (left) CPS range and latch, click lists and grids use their own delays.
+fn spawn_mode_scheduler(clicker: AutoClicker) {
+    thread::spawn(move || {
+        let mut rng = rand::thread_rng();
+        let mut trigger = Trigger::new(clicker.left_latched.clone());
+        let mut step = 0;
+        let mut loops_done = 0;
+        // The user script with the version it was loaded from, Lua state stays on this thread
+        let mut script: Option<(usize, Script)> = None;
+
+        loop {
+            let steps = clicker.mode_steps();
+
+            // Start the sequence over on every new activation. Macros start and stop with the
+            // clicker alone, a held trigger button would mix with the macro's own presses.
+            let enabled = clicker.is_running() && !steps.is_empty();
+            let gated = steps != [Step::Macro];
+            let active = trigger.active(&clicker, enabled && gated, clicker.any_pressed(), clicker.any_presses());
+            if !(active || enabled && !gated) {
+                step = 0;
+                loops_done = 0;
+                if let Some((_, script)) = &mut script {
+                    script.reset();
+                }
+                thread::sleep(Duration::from_millis(10));
+                continue;
+            }
+
+            let started = Instant::now();
+            let current = &steps[step % steps.len()];
+            if *current != Step::Rest && !clicker.own_window_focused() {
+                match current {
+                    Step::Click(button) => clicker.click_unit(*button),
+                    Step::Scroll(direction, notches) => send_scroll(*direction, *notches),
+                    Step::Keys(keys) => send_keys(keys),
+                    Step::Type => clicker.type_text(&mut rng),
+                    Step::Script => clicker.run_script(&mut script),
+                    Step::Macro => {
+                        // Every loop played, the clicker's job is done
+                        if clicker.play_macro() {
+                            clicker.is_running.store(false, Ordering::Relaxed);
+                        }
+                    }
+                    Step::Point(point) => match point.drag_to {
+                        Some((to_x, to_y)) => clicker.drag((point.x, point.y), (to_x, to_y), point.button),
+                        None => clicker.click_at(point.x, point.y, point.button, point.clicks),
+                    },
+                    Step::Rest => {}
+                }
+                if gated {
+                    trigger.clicked(&clicker, clicker.any_presses());
+                }
+            }
+            let finished_loop = step + 1 == steps.len();
+            step = (step + 1) % steps.len();
+
+            match current {
+                // Click list points carry their own delay and stop the clicker after the last loop
+                Step::Point(point) => {
+                    sleep_while_running(&clicker, Duration::from_millis(point.delay_ms as u64));
+                    if finished_loop {
+                        // New shuffle for the next pass over the grid
+                        clicker.grid_seed.fetch_add(1, Ordering::Relaxed);
+                        loops_done += 1;
+                        let loops = clicker.list_loops.load(Ordering::Relaxed);
+                        if loops != 0 && loops_done >= loops {
+                            clicker.is_running.store(false, Ordering::Relaxed);
+                        }
+                    }
+                }
+                _ => sleep_rest_of_cycle(&mut rng, &clicker.left_min_cps, &clicker.left_max_cps, started),
+            }
+        }
+    });
+}
+
 impl AutoClicker {
+    fn own_window_focused(&self) -> bool {
+        self.window_titles.contains(&get_active_window_title())
+    }
+
     pub fn new() -> Self {
         let auto_clicker = Self::default();
         
-        // Start the clicker thread
+        // Shared state for the input monitoring threads
         let is_running_clicker = auto_clicker.is_running.clone();
-        let click_mode = auto_clicker.click_mode.clone();
-        let min_cps = auto_clicker.min_cps.clone();
-        let max_cps = auto_clicker.max_cps.clone();
-        let left_pressed = auto_clicker.left_pressed.clone();
-        let right_pressed = auto_clicker.right_pressed.clone();
-        let window_title = auto_clicker.window_title.clone();
+        let mouse_inputs = auto_clicker.mouse.clone();
+        let key_triggers = [
+            (auto_clicker.left_trigger_key.clone(), auto_clicker.left_key.clone()),
+            (auto_clicker.right_trigger_key.clone(), auto_clicker.right_key.clone()),
+        ];
         
         #[cfg(target_os = "windows")]
         {
             // Set up the global state
             unsafe {
-                LEFT_PRESSED = Some(left_pressed.clone());
-                RIGHT_PRESSED = Some(right_pressed.clone());
+                MOUSE_INPUTS = Some(mouse_inputs.clone());
+                KEY_TRIGGERS = key_triggers.to_vec();
                 IS_RUNNING = Some(is_running_clicker.clone());
+                CLICKER = Some(auto_clicker.clone());
             }
             
             // Start mouse hook thread
@@ -272,9 +1072,10 @@ impl AutoClicker {
         
         #[cfg(target_os = "linux")]
         {
+            // Side buttons are not in the XQueryPointer mask and recording needs every event, XInput2 reports both
+            spawn_xi2_monitor(auto_clicker.clone());
+
             // Start X11 event monitoring thread
-            let left_pressed = left_pressed.clone();
-            let right_pressed = right_pressed.clone();
             let is_running_event = is_running_clicker.clone();
             
             thread::spawn(move || {
@@ -321,16 +1122,34 @@ impl AutoClicker {
                         if result != 0 {
                             // Check for left mouse button (Button1Mask)
                             let left_state = (mask_return & x11::xlib::Button1Mask) != 0;
-                            left_pressed.store(left_state, Ordering::Relaxed);
+                            mouse_inputs.left.set(left_state);
                             
                             // Check for right mouse button (Button3Mask)
                             let right_state = (mask_return & x11::xlib::Button3Mask) != 0;
-                            right_pressed.store(right_state, Ordering::Relaxed);
+                            mouse_inputs.right.set(right_state);
+
+                            // Check for middle mouse button (Button2Mask)
+                            let middle_state = (mask_return & x11::xlib::Button2Mask) != 0;
+                            mouse_inputs.middle.set(middle_state);
 
 
                         } else {
                             println!("XQueryPointer failed");
                         }
+
+                        // Check keys bound as click triggers
+                        let mut key_states = [0; 32];
+                        x11::xlib::XQueryKeymap(display, key_states.as_mut_ptr());
+                        for (trigger_key, input) in key_triggers.iter() {
+                            let down = match Key::from_usize(trigger_key.load(Ordering::Relaxed)) {
+                                Some(key) => {
+                                    let keycode = x11::xlib::XKeysymToKeycode(display, key.keysym() as u64) as usize;
+                                    keycode != 0 && (key_states[keycode / 8] & (1 << (keycode % 8))) != 0
+                                }
+                                None => false,
+                            };
+                            input.set(down);
+                        }
                         
                         // Check for F6 key press without blocking
                         while x11::xlib::XPending(display) > 0 {
@@ -343,8 +1162,8 @@ impl AutoClicker {
                                     
                                     // Reset button states when toggling
                                     if !is_running_event.load(Ordering::Relaxed) {
-                                        left_pressed.store(false, Ordering::Relaxed);
-                                        right_pressed.store(false, Ordering::Relaxed);
+                                        mouse_inputs.left.set(false);
+                                        mouse_inputs.right.set(false);
                                     }
                                 }
                             }
@@ -357,180 +1176,19 @@ impl AutoClicker {
             });
         }
         
-        // Start clicker thread
-        let min_cps_clone = auto_clicker.min_cps.clone();
-        let max_cps_clone = auto_clicker.max_cps.clone();
-        thread::spawn(move || {
-            let mut rng = rand::thread_rng();
-            
-            loop {
-                if is_running_clicker.load(Ordering::Relaxed) {
-                    // Check if foreground window is not our application
-                    let current_title = {
-                        #[cfg(target_os = "windows")]
-                        unsafe {
-                            let hwnd = GetForegroundWindow();
-                            let mut title = [0u16; 512];
-                            let len = GetWindowTextW(hwnd, title.as_mut_ptr(), title.len() as i32);
-                            if len > 0 {
-                                OsString::from_wide(&title[..len as usize]).to_string_lossy().into_owned()
-                            } else {
-                                String::new()
-                            }
-                        }
-                        
-                        #[cfg(target_os = "linux")]
-                        get_active_window_title()
-                    };
-                    
-                    if current_title != window_title {
-                        let left_state = left_pressed.load(Ordering::Relaxed);
-                        let right_state = right_pressed.load(Ordering::Relaxed);
-                        let current_mode = ClickMode::from_usize(click_mode.load(Ordering::Relaxed));
-                        
-                        match current_mode {
-                            ClickMode::Left => {
-                                if left_state {
-                                    #[cfg(target_os = "windows")]
-                                    unsafe {
-                                        mouse_event(MOUSEEVENTF_LEFTDOWN, 0, 0, 0, 0);
-                                        thread::sleep(Duration::from_millis(1));
-                                        mouse_event(MOUSEEVENTF_LEFTUP, 0, 0, 0, 0);
-                                    }
-                                    #[cfg(target_os = "linux")]
-                                    unsafe {
-                                        let target_window = get_active_window();
-                                        if target_window != 0 {
-                                            let xdo = xdo_new(std::ptr::null());
-                                            if !xdo.is_null() {
-                                                xdo_mouse_down(xdo, target_window, 1);
-                                                thread::sleep(Duration::from_millis(1));
-                                                xdo_mouse_up(xdo, target_window, 1);
-                                                xdo_free(xdo);
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                            ClickMode::Right => {
-                                if right_state {
-                                    #[cfg(target_os = "windows")]
-                                    unsafe {
-                                        mouse_event(MOUSEEVENTF_RIGHTDOWN, 0, 0, 0, 0);
-                                        thread::sleep(Duration::from_millis(1));
-                                        mouse_event(MOUSEEVENTF_RIGHTUP, 0, 0, 0, 0);
-                                    }
-                                    #[cfg(target_os = "linux")]
-                                    unsafe {
-                                        let target_window = get_active_window();
-                                        if target_window != 0 {
-                                            let xdo = xdo_new(std::ptr::null());
-                                            if !xdo.is_null() {
-                                                xdo_mouse_down(xdo, target_window, 3);
-                                                thread::sleep(Duration::from_millis(1));
-                                                xdo_mouse_up(xdo, target_window, 3);
-                                                xdo_free(xdo);
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                            ClickMode::Both => {
-                                if left_state {
-                                    #[cfg(target_os = "windows")]
-                                    unsafe {
-                                        mouse_event(MOUSEEVENTF_LEFTDOWN, 0, 0, 0, 0);
-                                        thread::sleep(Duration::from_millis(1));
-                                        mouse_event(MOUSEEVENTF_LEFTUP, 0, 0, 0, 0);
-                                    }
-                                    #[cfg(target_os = "linux")]
-                                    unsafe {
-                                        let target_window = get_active_window();
-                                        if target_window != 0 {
-                                            let xdo = xdo_new(std::ptr::null());
-                                            if !xdo.is_null() {
-                                                xdo_mouse_down(xdo, target_window, 1);
-                                                thread::sleep(Duration::from_millis(1));
-                                                xdo_mouse_up(xdo, target_window, 1);
-                                                xdo_free(xdo);
-                                            }
-                                        }
-                                    }
-                                }
-                                if right_state {
-                                    #[cfg(target_os = "windows")]
-                                    unsafe {
-                                        mouse_event(MOUSEEVENTF_RIGHTDOWN, 0, 0, 0, 0);
-                                        thread::sleep(Duration::from_millis(1));
-                                        mouse_event(MOUSEEVENTF_RIGHTUP, 0, 0, 0, 0);
-                                    }
-                                    #[cfg(target_os = "linux")]
-                                    unsafe {
-                                        let target_window = get_active_window();
-                                        if target_window != 0 {
-                                            let xdo = xdo_new(std::ptr::null());
-                                            if !xdo.is_null() {
-                                                xdo_mouse_down(xdo, target_window, 3);
-                                                thread::sleep(Duration::from_millis(1));
-                                                xdo_mouse_up(xdo, target_window, 3);
-                                                xdo_free(xdo);
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    
-                    // Load current min/max CPS values and calculate delay
-                    let current_min = min_cps_clone.load(Ordering::SeqCst);
-                    let current_max = max_cps_clone.load(Ordering::SeqCst);
-                    
-                    // Use the actual slider values for random CPS generation
-                    let cps = rng.gen_range(current_min..=current_max);
-                    
-                    // Calculate total time for one click cycle (including click duration)
-                    // 1000ms / CPS gives us the total time per click cycle
-                    // Subtract 2ms for the click duration (1ms down + 1ms up)
-                    let total_cycle_time = (1000.0 / cps as f32) as u64;
-                    let delay_ms = total_cycle_time.saturating_sub(2);
-                    
-                    // Sleep for the calculated delay
-                    thread::sleep(Duration::from_millis(delay_ms));
-                } else {
-                    thread::sleep(Duration::from_millis(10));
-                }
-            }
-        });
+        // Start one scheduler thread per button so each has its own rate
+        for button in [MouseButton::Left, MouseButton::Right] {
+            spawn_scheduler(button, auto_clicker.clone());
+        }
+        spawn_mode_scheduler(auto_clicker.clone());
 
         // Start the hotkey thread with its own clone of is_running
         let _is_running_hotkey = auto_clicker.is_running.clone();
+        let _clicker_hotkey = auto_clicker.clone();
         thread::spawn(move || {
-            #[cfg(target_os = "windows")]
-            unsafe {
-                let hook = SetWindowsHookExW(
-                    WH_KEYBOARD_LL,
-                    Some(keyboard_hook_proc),
-                    std::ptr::null_mut(),
-                    0,
-                );
-                
-                if hook.is_null() {
-                    println!("Failed to set keyboard hook");
-                    return;
-                }
-                
-                // Keep the hook alive
-                let mut msg: MSG = zeroed();
-                while GetMessageW(&mut msg, std::ptr::null_mut(), 0, 0) != 0 {
-                    TranslateMessage(&msg);
-                    DispatchMessageW(&msg);
-                }
-                
-                UnhookWindowsHookEx(hook);
-            }
-
-            #[cfg(target_os = "linux")]
+            // On Windows the keyboard hook thread above handles the hotkeys, a second hook
+            // would see every key twice
+            #[cfg(target_os = "linux")]
             unsafe {
                 let display = x11::xlib::XOpenDisplay(std::ptr::null());
                 if display.is_null() {
@@ -539,7 +1197,11 @@ impl AutoClicker {
                 }
 
                 let f6_keycode = x11::xlib::XKeysymToKeycode(display, x11::keysym::XK_F6 as u64);
+                let f7_keycode = x11::xlib::XKeysymToKeycode(display, x11::keysym::XK_F7 as u64);
+                let f8_keycode = x11::xlib::XKeysymToKeycode(display, x11::keysym::XK_F8 as u64);
                 let mut key_states = [0; 256];
+                let mut f7_was_pressed = false;
+                let mut f8_was_pressed = false;
 
                 loop {
                     x11::xlib::XQueryKeymap(display, key_states.as_mut_ptr());
@@ -548,6 +1210,20 @@ impl AutoClicker {
                         _is_running_hotkey.store(!_is_running_hotkey.load(Ordering::Relaxed), Ordering::Relaxed);
                         thread::sleep(Duration::from_millis(200)); // Debounce
                     }
+
+                    // F7 captures the pointer position into the click list, once per press
+                    let f7_pressed = (key_states[f7_keycode as usize / 8] & (1 << (f7_keycode % 8))) != 0;
+                    if f7_pressed && !f7_was_pressed {
+                        _clicker_hotkey.capture_point();
+                    }
+                    f7_was_pressed = f7_pressed;
+
+                    // F8 starts and stops macro recording
+                    let f8_pressed = (key_states[f8_keycode as usize / 8] & (1 << (f8_keycode % 8))) != 0;
+                    if f8_pressed && !f8_was_pressed {
+                        _clicker_hotkey.toggle_recording();
+                    }
+                    f8_was_pressed = f8_pressed;
                     thread::sleep(Duration::from_millis(10));
                 }
             }
@@ -556,18 +1232,794 @@ impl AutoClicker {
         auto_clicker
     }
 
-    pub fn set_min_cps(&self, value: u32) {
-        self.min_cps.store(value, Ordering::SeqCst);
+    fn cps_range(&self, button: MouseButton) -> (&Arc<AtomicU32>, &Arc<AtomicU32>) {
+        // Middle clicks only come from patterns, which run on the primary range
+        match button {
+            MouseButton::Right => (&self.right_min_cps, &self.right_max_cps),
+            _ => (&self.left_min_cps, &self.left_max_cps),
+        }
+    }
+
+    pub fn set_min_cps(&self, button: MouseButton, value: u32) {
+        self.cps_range(button).0.store(value, Ordering::SeqCst);
     }
 
-    pub fn set_max_cps(&self, value: u32) {
-        self.max_cps.store(value, Ordering::SeqCst);
+    pub fn set_max_cps(&self, button: MouseButton, value: u32) {
+        self.cps_range(button).1.store(value, Ordering::SeqCst);
     }
 
     pub fn set_click_mode(&self, mode: ClickMode) {
         self.click_mode.store(mode.to_usize(), Ordering::Relaxed);
     }
 
+    pub fn set_pattern(&self, pattern: &str) {
+        *self.pattern.lock().unwrap() = pattern.to_string();
+    }
+
+    pub fn set_key_combo(&self, combo: &str) {
+        *self.key_combo.lock().unwrap() = combo.to_string();
+    }
+
+    pub fn set_type_text(&self, text: &str) {
+        *self.type_text.lock().unwrap() = text.to_string();
+    }
+
+    pub fn set_type_from_clipboard(&self, from_clipboard: bool) {
+        self.type_from_clipboard.store(from_clipboard, Ordering::Relaxed);
+    }
+
+    pub fn set_type_delay_ms(&self, min_ms: u32, max_ms: u32) {
+        self.type_min_delay_ms.store(min_ms, Ordering::Relaxed);
+        self.type_max_delay_ms.store(max_ms, Ordering::Relaxed);
+    }
+
+    pub fn set_newline(&self, newline: Newline) {
+        self.newline.store(newline.to_usize(), Ordering::Relaxed);
+    }
+
+    // Type the text (or clipboard) one character at a time. Stops as soon as the clicker is
+    // stopped or our own window takes focus, so F6 works mid-text.
+    fn type_text(&self, rng: &mut impl Rng) {
+        let text = if self.type_from_clipboard.load(Ordering::Relaxed) {
+            arboard::Clipboard::new()
+                .and_then(|mut clipboard| clipboard.get_text())
+                .unwrap_or_default()
+        } else {
+            self.type_text.lock().unwrap().clone()
+        };
+        let newline = Newline::from_usize(self.newline.load(Ordering::Relaxed));
+
+        for c in text.chars() {
+            if !self.is_running() || self.own_window_focused() {
+                return;
+            }
+
+            match (c, newline) {
+                ('\r', _) | ('\n', Newline::Skip) => continue,
+                ('\n', Newline::Enter) => send_keys(&[Key::from_name("Enter").unwrap()]),
+                ('\n', Newline::ShiftEnter) => {
+                    send_keys(&[Key::from_name("Shift").unwrap(), Key::from_name("Enter").unwrap()])
+                }
+                _ => send_text(&c.to_string()),
+            }
+
+            let min_ms = self.type_min_delay_ms.load(Ordering::Relaxed);
+            let max_ms = self.type_max_delay_ms.load(Ordering::Relaxed).max(min_ms);
+            thread::sleep(Duration::from_millis(rng.gen_range(min_ms..=max_ms) as u64));
+        }
+    }
+
+    pub fn set_scroll_direction(&self, direction: ScrollDirection) {
+        self.scroll_direction.store(direction.to_usize(), Ordering::Relaxed);
+    }
+
+    pub fn set_scroll_notches(&self, notches: u32) {
+        self.scroll_notches.store(notches, Ordering::Relaxed);
+    }
+
+    pub fn set_burst_clicks(&self, clicks: u32) {
+        self.burst_clicks.store(clicks, Ordering::Relaxed);
+    }
+
+    pub fn set_burst_gap_ms(&self, gap_ms: u32) {
+        self.burst_gap_ms.store(gap_ms, Ordering::Relaxed);
+    }
+
+    // Modifier keys held down around every click burst, e.g. Ctrl for multi-selection
+    pub fn set_click_modifiers(&self, keys: &[Key]) {
+        *self.click_modifiers.lock().unwrap() = keys.to_vec();
+    }
+
+    pub fn set_click_target(&self, target: ClickTarget) {
+        self.click_target.store(target.to_usize(), Ordering::Relaxed);
+    }
+
+    pub fn set_target_position(&self, x: i32, y: i32) {
+        self.target_x.store(x, Ordering::Relaxed);
+        self.target_y.store(y, Ordering::Relaxed);
+    }
+
+    // Put the pointer back where the user left it after clicking a fixed point
+    pub fn set_restore_pointer(&self, restore: bool) {
+        self.restore_pointer.store(restore, Ordering::Relaxed);
+    }
+
+    pub fn click_points(&self) -> Vec<ClickPoint> {
+        self.click_points.lock().unwrap().clone()
+    }
+
+    // Edits the shared list in place, so a point captured while the GUI was editing isn't lost
+    pub fn edit_click_points(&self, edit: impl FnOnce(&mut Vec<ClickPoint>)) {
+        edit(&mut self.click_points.lock().unwrap());
+    }
+
+    // How many times the click list runs before the clicker stops, 0 loops forever
+    pub fn set_list_loops(&self, loops: u32) {
+        self.list_loops.store(loops, Ordering::Relaxed);
+    }
+
+    // Title or class of the window that saved points are relative to, empty for screen coordinates
+    pub fn set_anchor_window(&self, query: &str) {
+        *self.anchor_window.lock().unwrap() = query.trim().to_string();
+    }
+
+    // Screen position saved points are offset from, None while the anchor window can't be found
+    pub fn anchor_origin(&self) -> Option<(i32, i32)> {
+        let query = self.anchor_window.lock().unwrap().clone();
+        if query.is_empty() {
+            Some((0, 0))
+        } else {
+            find_window_origin(&query)
+        }
+    }
+
+    // The pointer position in saved-point coordinates
+    pub fn capture_position(&self) -> Option<(i32, i32)> {
+        let (origin_x, origin_y) = self.anchor_origin()?;
+        let (x, y) = cursor_position();
+        Some((x - origin_x, y - origin_y))
+    }
+
+    // Append the pointer position to the click list, bound to the capture hotkey
+    fn capture_point(&self) {
+        if let Some((x, y)) = self.capture_position() {
+            self.click_points.lock().unwrap().push(ClickPoint::at(x, y));
+        }
+    }
+
+    // Rectangle as x, y, width, height in saved-point coordinates
+    pub fn set_region(&self, x: i32, y: i32, width: i32, height: i32) {
+        *self.region.lock().unwrap() = (x, y, width.max(1), height.max(1));
+    }
+
+    // Keep random region clicks at least this many pixels from the previous one
+    pub fn set_region_min_distance(&self, distance: u32) {
+        self.region_min_distance.store(distance, Ordering::Relaxed);
+    }
+
+    // A uniformly random point in the region, retrying a few times to honour the minimum
+    // distance before settling for the furthest candidate
+    fn random_region_point(&self) -> (i32, i32) {
+        let mut rng = rand::thread_rng();
+        let (x, y, width, height) = *self.region.lock().unwrap();
+        let min_distance = self.region_min_distance.load(Ordering::Relaxed) as f64;
+        let mut last = self.last_region_point.lock().unwrap();
+
+        let distance = |(px, py): (i32, i32)| {
+            last.map_or(f64::MAX, |(lx, ly)| (((px - lx) as f64).powi(2) + ((py - ly) as f64).powi(2)).sqrt())
+        };
+        let mut best: Option<((i32, i32), f64)> = None;
+        for _ in 0..32 {
+            let candidate = (x + rng.gen_range(0..width.max(1)), y + rng.gen_range(0..height.max(1)));
+            let candidate_distance = distance(candidate);
+            if best.is_none_or(|(_, best_distance)| candidate_distance > best_distance) {
+                best = Some((candidate, candidate_distance));
+            }
+            if candidate_distance >= min_distance {
+                break;
+            }
+        }
+
+        let point = best.map_or((x, y), |(point, _)| point);
+        *last = Some(point);
+        point
+    }
+
+    pub fn set_grid_size(&self, rows: u32, columns: u32) {
+        self.grid_rows.store(rows.max(1), Ordering::Relaxed);
+        self.grid_columns.store(columns.max(1), Ordering::Relaxed);
+    }
+
+    pub fn set_grid_order(&self, order: GridOrder) {
+        self.grid_order.store(order.to_usize(), Ordering::Relaxed);
+    }
+
+    pub fn set_grid_delay_ms(&self, delay_ms: u32) {
+        self.grid_delay_ms.store(delay_ms, Ordering::Relaxed);
+    }
+
+    // One click on the centre of every cell of the grid laid over the region
+    fn grid_steps(&self) -> Vec<Step> {
+        let (x, y, width, height) = *self.region.lock().unwrap();
+        let rows = self.grid_rows.load(Ordering::Relaxed).max(1);
+        let columns = self.grid_columns.load(Ordering::Relaxed).max(1);
+        let order = GridOrder::from_usize(self.grid_order.load(Ordering::Relaxed));
+        let seed = self.grid_seed.load(Ordering::Relaxed) as u64;
+        let clicks = self.burst_clicks.load(Ordering::Relaxed);
+        let delay_ms = self.grid_delay_ms.load(Ordering::Relaxed);
+
+        grid_cells(rows, columns, order, seed)
+            .into_iter()
+            .map(|(row, column)| {
+                Step::Point(ClickPoint {
+                    x: x + ((2 * column + 1) as i64 * width as i64 / (2 * columns) as i64) as i32,
+                    y: y + ((2 * row + 1) as i64 * height as i64 / (2 * rows) as i64) as i32,
+                    button: MouseButton::Left,
+                    clicks,
+                    delay_ms,
+                    drag_to: None,
+                })
+            })
+            .collect()
+    }
+
+    pub fn set_motion_path(&self, path: MotionPath) {
+        self.motion_path.store(path.to_usize(), Ordering::Relaxed);
+    }
+
+    // How long a move takes and how many pointer updates per second it is made of
+    pub fn set_motion_timing(&self, duration_ms: u32, rate: u32) {
+        self.motion_duration_ms.store(duration_ms, Ordering::Relaxed);
+        self.motion_rate.store(rate.max(1), Ordering::Relaxed);
+    }
+
+    // Move the pointer along the configured path so hover and drag handlers see every step.
+    // Returns false if the clicker was stopped on the way.
+    fn glide_to(&self, x: i32, y: i32) -> bool {
+        self.glide(x, y, MotionPath::from_usize(self.motion_path.load(Ordering::Relaxed)))
+    }
+
+    fn glide(&self, x: i32, y: i32, path: MotionPath) -> bool {
+        let duration = Duration::from_millis(self.motion_duration_ms.load(Ordering::Relaxed) as u64);
+        let rate = self.motion_rate.load(Ordering::Relaxed).max(1);
+        let steps = (duration.as_secs_f64() * rate as f64).ceil() as u32;
+        if path == MotionPath::Teleport || steps < 2 {
+            move_pointer(x, y);
+            return true;
+        }
+
+        let from = cursor_position();
+        let (start, end) = ((from.0 as f64, from.1 as f64), (x as f64, y as f64));
+
+        // Curve control points pushed sideways by a random share of the distance
+        let mut rng = rand::thread_rng();
+        let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+        let control = |along: f64, side: f64| (start.0 + dx * along - dy * side, start.1 + dy * along + dx * side);
+        let control1 = control(0.3, rng.gen_range(-0.3..=0.3));
+        let control2 = control(0.7, rng.gen_range(-0.3..=0.3));
+
+        let started = Instant::now();
+        for step in 1..=steps {
+            if !self.is_running() {
+                return false;
+            }
+
+            let t = step as f64 / steps as f64;
+            let (px, py) = match path {
+                MotionPath::Linear => (start.0 + dx * t, start.1 + dy * t),
+                MotionPath::Eased => (start.0 + dx * ease(t), start.1 + dy * ease(t)),
+                _ => cubic_bezier(start, control1, control2, end, ease(t)),
+            };
+            move_pointer(px.round() as i32, py.round() as i32);
+
+            // Sleep to the step's slot on the clock so slow moves don't drift
+            let due = duration.mul_f64(t);
+            if let Some(wait) = due.checked_sub(started.elapsed()) {
+                thread::sleep(wait);
+            }
+        }
+        true
+    }
+
+    // Start and end of the standalone drag, in saved-point coordinates
+    pub fn set_drag(&self, from: (i32, i32), to: (i32, i32)) {
+        *self.drag_from.lock().unwrap() = from;
+        *self.drag_to.lock().unwrap() = to;
+    }
+
+    pub fn set_drag_delay_ms(&self, delay_ms: u32) {
+        self.drag_delay_ms.store(delay_ms, Ordering::Relaxed);
+    }
+
+    // Press at one saved point, glide to another with the button (and any modifiers) held, and
+    // release there. The button comes back up even if the clicker is stopped mid-drag.
+    fn drag(&self, from: (i32, i32), to: (i32, i32), button: MouseButton) {
+        let Some((origin_x, origin_y)) = self.anchor_origin() else {
+            return;
+        };
+
+        let return_to = cursor_position();
+        if !self.glide_to(origin_x + from.0, origin_y + from.1) {
+            return;
+        }
+        thread::sleep(Duration::from_millis(5));
+
+        {
+            let _modifiers = hold_keys(&self.click_modifiers.lock().unwrap());
+            self.drag_from_here(origin_x + to.0, origin_y + to.1, button);
+        }
+
+        if self.restore_pointer.load(Ordering::Relaxed) {
+            move_pointer(return_to.0, return_to.1);
+        }
+    }
+
+    // Hold the button from the current pointer position to a screen point
+    fn drag_from_here(&self, x: i32, y: i32, button: MouseButton) {
+        let _button = hold_button(button);
+
+        // Let the target pass its drag threshold timing before moving, and a teleport
+        // would skip the motion drag handlers need to see
+        thread::sleep(Duration::from_millis(50));
+        let path = match MotionPath::from_usize(self.motion_path.load(Ordering::Relaxed)) {
+            MotionPath::Teleport => MotionPath::Linear,
+            path => path,
+        };
+        if self.glide(x, y, path) {
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    pub fn start_recording(&self) {
+        *self.recorder.lock().unwrap() = Recorder::default();
+        self.recording.store(true, Ordering::Relaxed);
+    }
+
+    // Stop recording and make the recording the current macro
+    pub fn stop_recording(&self) {
+        if self.recording.swap(false, Ordering::Relaxed) {
+            let recorder = std::mem::take(&mut *self.recorder.lock().unwrap());
+            *self.macro_steps.lock().unwrap() = recorder.finish();
+        }
+    }
+
+    pub fn toggle_recording(&self) {
+        if self.is_recording() {
+            self.stop_recording();
+        } else {
+            self.start_recording();
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.load(Ordering::Relaxed)
+    }
+
+    // Fed by the platform hooks with the user's real input
+    fn record(&self, input: RecordedInput) {
+        if self.is_recording() {
+            self.recorder.lock().unwrap().record(input);
+        }
+    }
+
+    pub fn macro_steps(&self) -> Vec<MacroStep> {
+        self.macro_steps.lock().unwrap().clone()
+    }
+
+    pub fn set_macro_steps(&self, steps: &[MacroStep]) {
+        *self.macro_steps.lock().unwrap() = steps.to_vec();
+    }
+
+    // Playback speed in percent, waits are divided by it
+    pub fn set_macro_speed(&self, percent: u32) {
+        self.macro_speed.store(percent.max(1), Ordering::Relaxed);
+    }
+
+    // How many times the macro plays before the clicker stops, 0 loops forever
+    pub fn set_macro_loops(&self, loops: u32) {
+        self.macro_loops.store(loops, Ordering::Relaxed);
+    }
+
+    // Replay moves relative to where the pointer was when playback began
+    pub fn set_macro_relative(&self, relative: bool) {
+        self.macro_relative.store(relative, Ordering::Relaxed);
+    }
+
+    // Step being played and the loop it is in, both counted from 1
+    pub fn macro_progress(&self) -> (usize, u32) {
+        (self.macro_step.load(Ordering::Relaxed), self.macro_loop.load(Ordering::Relaxed))
+    }
+
+    // Start the clicker playing the macro once from a top level step. Macro mode isn't gated
+    // by the activation trigger, so this runs straight away.
+    pub fn run_macro_from(&self, step: usize) {
+        self.macro_start.store(step, Ordering::Relaxed);
+        self.macro_once.store(true, Ordering::Relaxed);
+        self.set_click_mode(ClickMode::Macro);
+        self.is_running.store(true, Ordering::Relaxed);
+    }
+
+    // Pause before every step until `step_macro` is called, for debugging a macro
+    pub fn set_macro_stepping(&self, stepping: bool) {
+        self.macro_stepping.store(stepping, Ordering::Relaxed);
+        self.macro_advance.store(false, Ordering::Relaxed);
+    }
+
+    pub fn step_macro(&self) {
+        self.macro_advance.store(true, Ordering::Relaxed);
+    }
+
+    // The step about to run and the macro's variables
+    pub fn macro_debug(&self) -> (String, Vec<(String, i64)>) {
+        (self.macro_next_step.lock().unwrap().clone(), self.macro_vars.lock().unwrap().clone())
+    }
+
+    // Play the macro through all its loops. Returns false if it was stopped part way, in which
+    // case any buttons and keys it was holding have been released.
+    fn play_macro(&self) -> bool {
+        let steps = self.macro_steps();
+        let loops = match self.macro_once.swap(false, Ordering::Relaxed) {
+            true => 1,
+            false => self.macro_loops.load(Ordering::Relaxed),
+        };
+
+        let start = self.macro_start.swap(0, Ordering::Relaxed).min(steps.len());
+
+        let offset = if self.macro_relative.load(Ordering::Relaxed) {
+            let (x, y) = cursor_position();
+            first_move(&steps[start..]).map_or((0, 0), |(first_x, first_y)| (x - first_x, y - first_y))
+        } else {
+            (0, 0)
+        };
+
+        // Variables carry over from one loop to the next
+        let mut interpreter = Interpreter::new(&steps);
+        let mut round = 0;
+        while loops == 0 || round < loops {
+            round += 1;
+            self.macro_loop.store(round, Ordering::Relaxed);
+            let from = if round == 1 { start } else { 0 };
+            if !self.play_steps(&steps, offset, &mut interpreter, Some(from)) {
+                return false;
+            }
+        }
+        true
+    }
+
+    // Hold playback until the GUI asks for the next step, false if stopped meanwhile
+    // Holds playback while one of our own windows has the focus, such as after pressing "Next
+    // step", so the step isn't sent into the clicker. False if the clicker was stopped.
+    fn wait_for_other_window(&self) -> bool {
+        while self.own_window_focused() {
+            if !self.is_running() {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        true
+    }
+
+    fn wait_for_step(&self) -> bool {
+        if !self.macro_stepping.load(Ordering::Relaxed) {
+            return true;
+        }
+        while self.is_running() {
+            if self.macro_advance.swap(false, Ordering::Relaxed) {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    // Play a block of steps. The top level is given the step to start from and reports its
+    // progress. Nothing pressed in a block stays held past its end.
+    fn play_steps(&self, steps: &[MacroStep], offset: (i32, i32), interpreter: &mut Interpreter, top_level: Option<usize>) -> bool {
+        let mut held_buttons = Vec::new();
+        let mut held_keys = Vec::new();
+
+        for (i, step) in steps.iter().enumerate().skip(top_level.unwrap_or(0)) {
+            if !self.is_running() {
+                return false;
+            }
+            // Definitions only run when called
+            if matches!(step, MacroStep::Sub(..)) {
+                continue;
+            }
+            if top_level.is_some() {
+                self.macro_step.store(i + 1, Ordering::Relaxed);
+            }
+            *self.macro_next_step.lock().unwrap() = step.to_string();
+            *self.macro_vars.lock().unwrap() = interpreter.vars();
+            if !self.wait_for_step() || !self.wait_for_other_window() {
+                return false;
+            }
+
+            match step {
+                MacroStep::Move(x, y) => move_pointer(x + offset.0, y + offset.1),
+                MacroStep::Click(button, clicks) => self.click_times(*button, *clicks),
+                MacroStep::Press(button) => held_buttons.push((*button, hold_button(*button))),
+                // Dropping the guard lets the button up
+                MacroStep::Release(button) => held_buttons.retain(|(held, _)| held != button),
+                MacroStep::Scroll(direction, notches) => send_scroll(*direction, *notches),
+                MacroStep::Keys(keys) => send_keys(keys),
+                MacroStep::KeyDown(key) => held_keys.push((*key, hold_keys(&[*key]))),
+                MacroStep::KeyUp(key) => held_keys.retain(|(held, _)| held != key),
+                MacroStep::Wait(ms) => {
+                    let speed = self.macro_speed.load(Ordering::Relaxed).max(1) as u64;
+                    sleep_while_running(self, Duration::from_millis(*ms as u64 * 100 / speed));
+                }
+                MacroStep::Drag(from, to, button) => {
+                    move_pointer(from.0 + offset.0, from.1 + offset.1);
+                    thread::sleep(Duration::from_millis(5));
+                    self.drag_from_here(to.0 + offset.0, to.1 + offset.1, *button);
+                }
+                MacroStep::Loop(count, body) => {
+                    for _ in 0..interpreter.value(count) {
+                        if !self.play_steps(body, offset, interpreter, None) {
+                            return false;
+                        }
+                    }
+                }
+                MacroStep::While(condition, body) => {
+                    while interpreter.test(condition) {
+                        if !self.is_running() || !self.play_steps(body, offset, interpreter, None) {
+                            return false;
+                        }
+                    }
+                }
+                MacroStep::If(condition, then, otherwise) => {
+                    let branch = if interpreter.test(condition) { then } else { otherwise };
+                    if !self.play_steps(branch, offset, interpreter, None) {
+                        return false;
+                    }
+                }
+                MacroStep::Set(name, value) => {
+                    let value = interpreter.value(value);
+                    interpreter.set(name, value);
+                }
+                MacroStep::Add(name, value) => {
+                    let sum = interpreter.value(&Value::Var(name.clone())).saturating_add(interpreter.value(value));
+                    interpreter.set(name, sum);
+                }
+                MacroStep::Call(name) => {
+                    // Runaway recursion stops the clicker like F6 would
+                    if interpreter.depth >= MAX_CALL_DEPTH {
+                        self.is_running.store(false, Ordering::Relaxed);
+                        return false;
+                    }
+                    let Some(body) = interpreter.sub(name) else {
+                        continue;
+                    };
+                    interpreter.depth += 1;
+                    let finished = self.play_steps(&body, offset, interpreter, None);
+                    interpreter.depth -= 1;
+                    if !finished {
+                        return false;
+                    }
+                }
+                MacroStep::Sub(..) => {}
+            }
+        }
+        true
+    }
+
+    // Takes effect the next time the script mode ticks, a running script is reloaded
+    pub fn set_script(&self, source: &str) {
+        *self.script_source.lock().unwrap() = source.to_string();
+        self.script_error.lock().unwrap().clear();
+        self.script_log.lock().unwrap().clear();
+        self.script_version.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Why the script last stopped the clicker, empty if it didn't
+    pub fn script_error(&self) -> String {
+        self.script_error.lock().unwrap().clone()
+    }
+
+    pub fn script_log(&self) -> Vec<String> {
+        self.script_log.lock().unwrap().clone()
+    }
+
+    // Keeps the most recent lines printed by the script
+    pub fn log_script(&self, line: &str) {
+        let mut log = self.script_log.lock().unwrap();
+        log.push(line.to_string());
+        if log.len() > 50 {
+            log.remove(0);
+        }
+    }
+
+    // One tick of the user script, loading it first if it changed. An error stops the clicker.
+    fn run_script(&self, loaded: &mut Option<(usize, Script)>) {
+        let version = self.script_version.load(Ordering::Relaxed);
+        if loaded.as_ref().is_none_or(|(loaded_version, _)| *loaded_version != version) {
+            *loaded = None;
+            let source = self.script_source.lock().unwrap().clone();
+            match Script::load(self, &source) {
+                Ok(script) => *loaded = Some((version, script)),
+                Err(error) => return self.script_failed(error),
+            }
+        }
+
+        if let Some((_, script)) = loaded {
+            if let Err(error) = script.tick() {
+                self.script_failed(error);
+            }
+        }
+    }
+
+    fn script_failed(&self, error: String) {
+        // Stopping the clicker interrupts the script too, that isn't its fault
+        if self.is_running() {
+            *self.script_error.lock().unwrap() = error;
+            self.is_running.store(false, Ordering::Relaxed);
+        }
+    }
+
+    // One scheduled click, sent as a burst of presses for double/triple-click targets.
+    // The gap is kept under the double-click time so the target sees a single multi-click.
+    fn click_unit(&self, button: MouseButton) {
+        let clicks = self.burst_clicks.load(Ordering::Relaxed);
+        match ClickTarget::from_usize(self.click_target.load(Ordering::Relaxed)) {
+            ClickTarget::Cursor => self.burst(button, clicks),
+            ClickTarget::Fixed => self.click_at(
+                self.target_x.load(Ordering::Relaxed),
+                self.target_y.load(Ordering::Relaxed),
+                button,
+                clicks,
+            ),
+            ClickTarget::Region => {
+                let (x, y) = self.random_region_point();
+                self.click_at(x, y, button, clicks);
+            }
+        }
+    }
+
+    // Move to a saved point, click there and optionally put the pointer back. Nothing is
+    // clicked while the anchor window is missing rather than hitting whatever is in its place.
+    fn click_at(&self, x: i32, y: i32, button: MouseButton, clicks: u32) {
+        let Some((origin_x, origin_y)) = self.anchor_origin() else {
+            return;
+        };
+
+        let return_to = cursor_position();
+        if !self.glide_to(origin_x + x, origin_y + y) {
+            return;
+        }
+        // Give the target a moment to see the hover before the press
+        thread::sleep(Duration::from_millis(5));
+
+        self.burst(button, clicks);
+
+        if self.restore_pointer.load(Ordering::Relaxed) {
+            move_pointer(return_to.0, return_to.1);
+        }
+    }
+
+    // A quick run of presses at the current pointer position, read as a double or triple click
+    pub fn burst(&self, button: MouseButton, clicks: u32) {
+        // Released when this goes out of scope, even if the burst panics
+        let _modifiers = hold_keys(&self.click_modifiers.lock().unwrap());
+        self.click_times(button, clicks);
+    }
+
+    // Clicks spaced by the burst gap, kept under the double-click time
+    fn click_times(&self, button: MouseButton, clicks: u32) {
+        let clicks = clicks.max(1);
+        let gap = Duration::from_millis(self.burst_gap_ms.load(Ordering::Relaxed) as u64)
+            .min(double_click_time().saturating_sub(Duration::from_millis(10)));
+
+        for i in 0..clicks {
+            if i > 0 {
+                thread::sleep(gap);
+            }
+            send_click(button);
+        }
+    }
+
+    pub fn set_activation(&self, activation: Activation) {
+        self.activation.store(activation.to_usize(), Ordering::Relaxed);
+    }
+
+    pub fn set_activation_clicks(&self, clicks: u32) {
+        self.activation_clicks.store(clicks, Ordering::Relaxed);
+    }
+
+    // Clicks sent since the current Count activation was armed
+    pub fn clicks_fired(&self) -> u32 {
+        self.clicks_fired.load(Ordering::Relaxed)
+    }
+
+    // Whether a button's stream is latched on, the sequence and scroll modes report as Left
+    pub fn is_latched(&self, button: MouseButton) -> bool {
+        match button {
+            MouseButton::Right => self.right_latched.load(Ordering::Relaxed),
+            _ => self.left_latched.load(Ordering::Relaxed),
+        }
+    }
+
+    // The steps the mode scheduler cycles through, empty for the per-button modes
+    fn mode_steps(&self) -> Vec<Step> {
+        match ClickMode::from_usize(self.click_mode.load(Ordering::Relaxed)) {
+            ClickMode::Alternate => vec![
+                Step::Click(MouseButton::Left),
+                Step::Click(MouseButton::Right),
+            ],
+            ClickMode::Pattern => parse_pattern(&self.pattern.lock().unwrap()).unwrap_or_default(),
+            ClickMode::Scroll => vec![Step::Scroll(
+                ScrollDirection::from_usize(self.scroll_direction.load(Ordering::Relaxed)),
+                self.scroll_notches.load(Ordering::Relaxed),
+            )],
+            ClickMode::Keys => parse_key_combo(&self.key_combo.lock().unwrap())
+                .map(|keys| vec![Step::Keys(keys)])
+                .unwrap_or_default(),
+            ClickMode::Type => vec![Step::Type],
+            ClickMode::List => self.click_points.lock().unwrap().iter().copied().map(Step::Point).collect(),
+            ClickMode::Grid => self.grid_steps(),
+            ClickMode::Drag => {
+                let (from, to) = (*self.drag_from.lock().unwrap(), *self.drag_to.lock().unwrap());
+                vec![Step::Point(ClickPoint {
+                    drag_to: Some(to),
+                    delay_ms: self.drag_delay_ms.load(Ordering::Relaxed),
+                    ..ClickPoint::at(from.0, from.1)
+                })]
+            }
+            ClickMode::Macro if !self.macro_steps.lock().unwrap().is_empty() => vec![Step::Macro],
+            ClickMode::Script => vec![Step::Script],
+            ClickMode::Left | ClickMode::Right | ClickMode::Both | ClickMode::Macro => Vec::new(),
+        }
+    }
+
+    fn activation(&self) -> Activation {
+        Activation::from_usize(self.activation.load(Ordering::Relaxed))
+    }
+
+    pub fn set_trigger_key(&self, button: MouseButton, key: Option<Key>) {
+        let trigger_key = match button {
+            MouseButton::Right => &self.right_trigger_key,
+            _ => &self.left_trigger_key,
+        };
+        trigger_key.store(Key::to_usize(key), Ordering::Relaxed);
+    }
+
+    // Which physical button activates a stream, e.g. X1 so the primary button stays free
+    pub fn set_trigger_button(&self, button: MouseButton, trigger: MouseButton) {
+        let trigger_button = match button {
+            MouseButton::Right => &self.right_trigger_button,
+            _ => &self.left_trigger_button,
+        };
+        trigger_button.store(trigger.to_usize(), Ordering::Relaxed);
+    }
+
+    fn trigger_inputs(&self, button: MouseButton) -> (&TriggerInput, &TriggerInput) {
+        let (trigger_button, key) = match button {
+            MouseButton::Right => (&self.right_trigger_button, &self.right_key),
+            _ => (&self.left_trigger_button, &self.left_key),
+        };
+        let trigger = MouseButton::from_usize(trigger_button.load(Ordering::Relaxed));
+        (self.mouse.get(trigger), key)
+    }
+
+    // The trigger button and the bound key both drive a button's stream
+    fn held(&self, button: MouseButton) -> bool {
+        let (mouse, key) = self.trigger_inputs(button);
+        mouse.held() || key.held()
+    }
+
+    fn presses(&self, button: MouseButton) -> u32 {
+        let (mouse, key) = self.trigger_inputs(button);
+        mouse.presses().wrapping_add(key.presses())
+    }
+
+    fn any_pressed(&self) -> bool {
+        self.held(MouseButton::Left) || self.held(MouseButton::Right)
+    }
+
+    fn any_presses(&self) -> u32 {
+        self.presses(MouseButton::Left).wrapping_add(self.presses(MouseButton::Right))
+    }
+
     pub fn toggle_running(&self) {
         let current = self.is_running.load(Ordering::Relaxed);
         self.is_running.store(!current, Ordering::Relaxed);
@@ -0,0 +1,467 @@
+use eframe::egui;
+use egui::text::LayoutJob;
+
+use crate::clicker::AutoClicker;
+use crate::macros::{button_name, direction_name, parse_color, parse_macro, to_text, MacroStep, ParseError, HEADER};
+
+// How many edits undo remembers
+const UNDO_LIMIT: usize = 100;
+
+const COMMENT_COLOR: egui::Color32 = egui::Color32::from_rgb(120, 120, 120);
+const KEYWORD_COLOR: egui::Color32 = egui::Color32::from_rgb(200, 130, 230);
+const COMMAND_COLOR: egui::Color32 = egui::Color32::from_rgb(80, 160, 255);
+const NUMBER_COLOR: egui::Color32 = egui::Color32::from_rgb(230, 160, 80);
+const TEXT_COLOR: egui::Color32 = egui::Color32::from_rgb(220, 220, 220);
+const ERROR_BACKGROUND: egui::Color32 = egui::Color32::from_rgba_premultiplied(90, 30, 30, 120);
+
+// Words that shape the control flow rather than send input
+fn is_keyword(word: &str) -> bool {
+    matches!(
+        word.to_lowercase().as_str(),
+        "loop" | "repeat" | "while" | "if" | "else" | "sub" | "call" | "set" | "add" | "not" | "window" | "pixel"
+            | "elapsed" | "{" | "}" | "<" | "<=" | ">" | ">=" | "==" | "!="
+    )
+}
+
+fn word_color(word: &str, first: bool) -> egui::Color32 {
+    let digits = word.strip_prefix(['-', 'x']).unwrap_or(word);
+    if is_keyword(word) {
+        KEYWORD_COLOR
+    } else if first {
+        COMMAND_COLOR
+    } else if let Some((r, g, b)) = parse_color(word) {
+        // Colour literals are shown in their own colour
+        egui::Color32::from_rgb(r, g, b)
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        NUMBER_COLOR
+    } else {
+        TEXT_COLOR
+    }
+}
+
+// Colours the macro text word by word, with a background on the line that failed to parse
+fn highlight(text: &str, error_line: Option<usize>) -> LayoutJob {
+    let mut job = LayoutJob::default();
+    for (number, line) in text.split_inclusive('\n').enumerate() {
+        let background = if error_line == Some(number) { ERROR_BACKGROUND } else { egui::Color32::TRANSPARENT };
+        let format = |color| egui::TextFormat {
+            font_id: egui::FontId::monospace(12.0),
+            color,
+            background,
+            ..Default::default()
+        };
+
+        if line.trim_start().starts_with('#') {
+            job.append(line, 0.0, format(COMMENT_COLOR));
+            continue;
+        }
+
+        let mut first = true;
+        let mut rest = line;
+        while !rest.is_empty() {
+            let space = rest.starts_with(char::is_whitespace);
+            let end = rest.find(|c: char| c.is_whitespace() != space).unwrap_or(rest.len());
+            let (token, tail) = rest.split_at(end);
+            let color = if space { TEXT_COLOR } else { word_color(token, std::mem::take(&mut first)) };
+            job.append(token, 0.0, format(color));
+            rest = tail;
+        }
+    }
+    job
+}
+
+// Nesting depth of every line and, for lines that start a top level step, that step's index
+fn line_info(lines: &[String]) -> Vec<(usize, Option<usize>)> {
+    let mut depth: usize = 0;
+    let mut steps = 0;
+    lines
+        .iter()
+        .map(|line| {
+            if line.starts_with('}') {
+                depth = depth.saturating_sub(1);
+            }
+            let step = if depth == 0 && !line.is_empty() && !line.starts_with('#') && !line.starts_with('}') {
+                steps += 1;
+                Some(steps - 1)
+            } else {
+                None
+            };
+            let line_depth = depth;
+            if line.ends_with('{') {
+                depth += 1;
+            }
+            (line_depth, step)
+        })
+        .collect()
+}
+
+// The step on a line that holds one simple step, blocks and blank lines give None
+fn parse_line(line: &str) -> Option<MacroStep> {
+    match parse_macro(&format!("{}\n{}", HEADER, line)) {
+        Ok(steps) if steps.len() == 1 => steps.into_iter().next(),
+        _ => None,
+    }
+}
+
+// Number fields for the coordinates, delays and counts of a step, None if it has none. The
+// ranges are the parser's, a DragValue clamps what it shows, and showing a step mustn't edit it.
+fn step_values(ui: &mut egui::Ui, step: &mut MacroStep) -> Option<egui::Response> {
+    let word = |ui: &mut egui::Ui, text: &str| {
+        ui.label(egui::RichText::new(text).monospace().color(COMMAND_COLOR));
+    };
+    match step {
+        MacroStep::Move(x, y) => {
+            word(ui, "move");
+            Some(ui.add(egui::DragValue::new(x)) | ui.add(egui::DragValue::new(y)))
+        }
+        MacroStep::Wait(ms) => {
+            word(ui, "wait");
+            Some(ui.add(egui::DragValue::new(ms).suffix("ms")))
+        }
+        MacroStep::Click(button, clicks) => {
+            word(ui, &format!("click {}", button_name(*button)));
+            Some(ui.add(egui::DragValue::new(clicks).prefix("x").clamp_range(1..=u32::MAX)))
+        }
+        MacroStep::Scroll(direction, notches) => {
+            word(ui, &format!("scroll {}", direction_name(*direction)));
+            Some(ui.add(egui::DragValue::new(notches).clamp_range(1..=u32::MAX)))
+        }
+        MacroStep::Drag(from, to, button) => {
+            word(ui, "drag");
+            let response = ui.add(egui::DragValue::new(&mut from.0))
+                | ui.add(egui::DragValue::new(&mut from.1))
+                | ui.add(egui::DragValue::new(&mut to.0))
+                | ui.add(egui::DragValue::new(&mut to.1));
+            word(ui, button_name(*button));
+            Some(response)
+        }
+        _ => None,
+    }
+}
+
+// A separate window for editing the macro as a list of lines or as text. Both views edit the
+// same lines, and every edit that parses goes straight to the clicker.
+#[derive(Default)]
+pub struct MacroEditor {
+    pub open: bool,
+    // Trimmed lines of the macro text without the header
+    lines: Vec<String>,
+    // The text view's contents, only re-indented when something else changes the lines
+    text: String,
+    show_text: bool,
+    undo: Vec<Vec<String>>,
+    redo: Vec<Vec<String>>,
+    // A run of typing in the text view is one undo step
+    typing: bool,
+    // Row being edited as raw text
+    editing: Option<(usize, String)>,
+    // Move the keyboard focus to the row editor on the next frame
+    focus_row: bool,
+    error: Option<ParseError>,
+    // What the clicker was last given, anything else was recorded or loaded elsewhere
+    synced: Vec<MacroStep>,
+}
+
+impl MacroEditor {
+    // Shows the editor window, returning the top level step to run from if one was picked
+    pub fn show(&mut self, ctx: &egui::Context, clicker: &AutoClicker) -> Option<usize> {
+        let steps = clicker.macro_steps();
+        if steps != self.synced {
+            if !self.lines.is_empty() {
+                self.checkpoint();
+            }
+            self.lines = to_text(&steps).lines().skip(1).map(|line| line.trim().to_string()).collect();
+            self.synced = steps;
+            self.error = None;
+            self.editing = None;
+            self.refresh_text();
+        }
+
+        let builder = egui::ViewportBuilder::default()
+            .with_title("Macro editor")
+            .with_inner_size([520.0, 460.0])
+            .with_min_inner_size([360.0, 240.0])
+            .with_resizable(true);
+
+        let mut run_from = None;
+        ctx.show_viewport_immediate(egui::ViewportId::from_hash_of("macro_editor"), builder, |ctx, _class| {
+            self.shortcuts(ctx, clicker);
+
+            egui::TopBottomPanel::top("macro_editor_toolbar").show(ctx, |ui| {
+                self.toolbar(ui, clicker);
+            });
+            egui::CentralPanel::default().show(ctx, |ui| {
+                if self.show_text {
+                    self.text_view(ui, clicker);
+                } else {
+                    run_from = self.step_list(ui, clicker);
+                }
+            });
+
+            if ctx.input(|i| i.viewport().close_requested()) {
+                self.open = false;
+            }
+        });
+        run_from
+    }
+
+    fn toolbar(&mut self, ui: &mut egui::Ui, clicker: &AutoClicker) {
+        ui.horizontal(|ui| {
+            if ui.selectable_label(!self.show_text, "Steps").clicked() {
+                self.show_text = false;
+                self.typing = false;
+            }
+            if ui.selectable_label(self.show_text, "Text").clicked() && !self.show_text {
+                self.show_text = true;
+                self.editing = None;
+                self.refresh_text();
+            }
+            ui.separator();
+            if ui.add_enabled(!self.undo.is_empty(), egui::Button::new("Undo")).clicked() {
+                self.undo(clicker);
+            }
+            if ui.add_enabled(!self.redo.is_empty(), egui::Button::new("Redo")).clicked() {
+                self.redo(clicker);
+            }
+        });
+
+        match &self.error {
+            Some(error) => {
+                ui.colored_label(egui::Color32::from_rgb(220, 80, 80), error.to_string());
+            }
+            None => {
+                ui.colored_label(egui::Color32::from_rgb(150, 150, 150), format!("{} steps", self.synced.len()));
+            }
+        }
+    }
+
+    // Ctrl+Z and Ctrl+Y (or Ctrl+Shift+Z), unless a text field has focus and its own undo
+    fn shortcuts(&mut self, ctx: &egui::Context, clicker: &AutoClicker) {
+        if ctx.memory(|memory| memory.focus().is_some()) {
+            return;
+        }
+
+        let shortcut = |modifiers, key| egui::KeyboardShortcut::new(modifiers, key);
+        let redo = ctx.input_mut(|i| {
+            i.consume_shortcut(&shortcut(egui::Modifiers::COMMAND | egui::Modifiers::SHIFT, egui::Key::Z))
+                || i.consume_shortcut(&shortcut(egui::Modifiers::COMMAND, egui::Key::Y))
+        });
+        if redo {
+            self.redo(clicker);
+        } else if ctx.input_mut(|i| i.consume_shortcut(&shortcut(egui::Modifiers::COMMAND, egui::Key::Z))) {
+            self.undo(clicker);
+        }
+    }
+
+    fn text_view(&mut self, ui: &mut egui::Ui, clicker: &AutoClicker) {
+        ui.colored_label(COMMENT_COLOR, HEADER);
+
+        // Parse errors count the header as line 1
+        let error_line = self.error.as_ref().and_then(|error| error.line.checked_sub(2));
+        let mut layouter = |ui: &egui::Ui, text: &str, wrap_width: f32| {
+            let mut job = highlight(text, error_line);
+            job.wrap.max_width = wrap_width;
+            ui.fonts(|fonts| fonts.layout_job(job))
+        };
+
+        let response = egui::ScrollArea::vertical()
+            .id_source("macro_text")
+            .show(ui, |ui| {
+                ui.add(
+                    egui::TextEdit::multiline(&mut self.text)
+                        .code_editor()
+                        .desired_width(f32::INFINITY)
+                        .desired_rows(20)
+                        .layouter(&mut layouter),
+                )
+            })
+            .inner;
+
+        if response.changed() {
+            if !self.typing {
+                self.checkpoint();
+                self.typing = true;
+            }
+            self.lines = self.text.lines().map(|line| line.trim().to_string()).collect();
+            self.apply(clicker);
+        }
+        if response.lost_focus() {
+            self.typing = false;
+        }
+    }
+
+    fn step_list(&mut self, ui: &mut egui::Ui, clicker: &AutoClicker) -> Option<usize> {
+        // Line numbers are only trustworthy while the text parses
+        let can_run = !clicker.is_running() && self.error.is_none();
+        let info = line_info(&self.lines);
+
+        let mut run_from = None;
+        let mut moved = None;
+        let mut remove = None;
+        let mut edited = None;
+        let mut snapshot = false;
+        let mut start_editing = None;
+        let mut stop_editing = false;
+
+        egui::ScrollArea::vertical()
+            .id_source("macro_rows")
+            .auto_shrink([false, false])
+            .max_height(ui.available_height() - 30.0)
+            .show(ui, |ui| {
+                for (i, line) in self.lines.iter().enumerate() {
+                    let (depth, step) = info[i];
+                    let row = ui.horizontal(|ui| {
+                        ui.dnd_drag_source(egui::Id::new(("macro_row", i)), i, |ui| {
+                            ui.label("☰");
+                        });
+
+                        // The middle of a block has no loop count or condition to go on
+                        let run = ui.add_enabled(can_run && step.is_some(), egui::Button::new("▶").small());
+                        if run.on_hover_text("Run from here").clicked() {
+                            run_from = step;
+                        }
+                        if ui.small_button("🗙").clicked() {
+                            remove = Some(i);
+                        }
+                        ui.add_space(depth as f32 * 14.0);
+
+                        match &mut self.editing {
+                            Some((row, buffer)) if *row == i => {
+                                let response = ui.add(egui::TextEdit::singleline(buffer).code_editor().desired_width(f32::INFINITY));
+                                if std::mem::take(&mut self.focus_row) {
+                                    response.request_focus();
+                                }
+                                if response.lost_focus() {
+                                    if !ui.input(|input| input.key_pressed(egui::Key::Escape)) {
+                                        edited = Some((i, buffer.trim().to_string()));
+                                        snapshot = true;
+                                    }
+                                    stop_editing = true;
+                                }
+                            }
+                            _ => {
+                                let values = parse_line(line).and_then(|mut step| {
+                                    step_values(ui, &mut step).map(|response| (step, response))
+                                });
+                                let response = match values {
+                                    Some((step, response)) => {
+                                        // A drag of a value is one undo step, typed values are one each
+                                        snapshot |= response.drag_started() || (response.changed() && !response.dragged());
+                                        if response.changed() {
+                                            edited = Some((i, step.to_string()));
+                                        }
+                                        response
+                                    }
+                                    None => ui.add(egui::Label::new(highlight(line, None)).sense(egui::Sense::click())),
+                                };
+                                if response.on_hover_text("Double-click to edit").double_clicked() {
+                                    start_editing = Some(i);
+                                }
+                            }
+                        }
+                    });
+
+                    if let Some(from) = row.response.dnd_release_payload::<usize>() {
+                        moved = Some((*from, i));
+                    }
+                    // Show where a dragged row will land
+                    if let Some(from) = row.response.dnd_hover_payload::<usize>() {
+                        let y = if *from < i { row.response.rect.bottom() } else { row.response.rect.top() };
+                        ui.painter().hline(row.response.rect.x_range(), y, egui::Stroke::new(2.0, COMMAND_COLOR));
+                    }
+                }
+            });
+
+        ui.horizontal(|ui| {
+            if ui.button("Add line").clicked() {
+                self.checkpoint();
+                self.lines.push(String::new());
+                self.editing = Some((self.lines.len() - 1, String::new()));
+                self.focus_row = true;
+                self.changed(clicker);
+            }
+            ui.colored_label(COMMENT_COLOR, "Drag ☰ to reorder");
+        });
+
+        if stop_editing {
+            self.editing = None;
+        }
+        if let Some(i) = start_editing {
+            self.editing = Some((i, self.lines[i].clone()));
+            self.focus_row = true;
+        }
+
+        if snapshot {
+            self.checkpoint();
+        }
+        if let Some((i, line)) = edited {
+            self.lines[i] = line;
+            self.changed(clicker);
+        }
+        if let Some(i) = remove {
+            self.checkpoint();
+            self.lines.remove(i);
+            self.editing = None;
+            self.changed(clicker);
+        }
+        if let Some((from, to)) = moved.filter(|(from, to)| from != to) {
+            self.checkpoint();
+            let line = self.lines.remove(from);
+            self.lines.insert(to, line);
+            self.editing = None;
+            self.changed(clicker);
+        }
+        run_from
+    }
+
+    fn checkpoint(&mut self) {
+        self.undo.push(self.lines.clone());
+        if self.undo.len() > UNDO_LIMIT {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    fn undo(&mut self, clicker: &AutoClicker) {
+        if let Some(lines) = self.undo.pop() {
+            self.redo.push(std::mem::replace(&mut self.lines, lines));
+            self.editing = None;
+            self.changed(clicker);
+        }
+    }
+
+    fn redo(&mut self, clicker: &AutoClicker) {
+        if let Some(lines) = self.redo.pop() {
+            self.undo.push(std::mem::replace(&mut self.lines, lines));
+            self.editing = None;
+            self.changed(clicker);
+        }
+    }
+
+    fn changed(&mut self, clicker: &AutoClicker) {
+        self.apply(clicker);
+        self.refresh_text();
+    }
+
+    // Hand the lines to the clicker if they parse, otherwise it keeps the last good macro
+    fn apply(&mut self, clicker: &AutoClicker) {
+        match parse_macro(&format!("{}\n{}", HEADER, self.lines.join("\n"))) {
+            Ok(steps) => {
+                clicker.set_macro_steps(&steps);
+                self.synced = steps;
+                self.error = None;
+            }
+            Err(error) => self.error = Some(error),
+        }
+    }
+
+    fn refresh_text(&mut self) {
+        let info = line_info(&self.lines);
+        self.text = self
+            .lines
+            .iter()
+            .zip(info)
+            .map(|(line, (depth, _))| format!("{}{}\n", "  ".repeat(depth), line))
+            .collect();
+    }
+}
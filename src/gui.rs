@@ -1,5 +1,24 @@
 use eframe::egui;
-use crate::clicker::{AutoClicker, ClickMode};
+use std::time::{Duration, Instant};
+use crate::clicker::{parse_pattern, Activation, AutoClicker, ClickMode, ClickPoint, ClickTarget, GridOrder, MotionPath, Newline};
+use crate::editor::MacroEditor;
+use crate::macros::{parse_macro, to_text};
+use crate::script::EXAMPLE_SCRIPT;
+use crate::xdotool::{export_click_list, export_macro, import_macro, write_script};
+use crate::input::{double_click_time, parse_key_combo, Key, MouseButton, ScrollDirection};
+
+// Result of the last file action, errors in red
+fn file_status(ui: &mut egui::Ui, status: &Result<String, String>) {
+    match status {
+        Ok(status) if status.is_empty() => {}
+        Ok(status) => {
+            ui.colored_label(egui::Color32::from_rgb(150, 150, 150), status);
+        }
+        Err(error) => {
+            ui.colored_label(egui::Color32::from_rgb(220, 80, 80), error);
+        }
+    }
+}
 
 fn custom_slider(ui: &mut egui::Ui, value: &mut u32, range: std::ops::RangeInclusive<u32>) -> bool {
     let desired_width = ui.available_width();
@@ -91,9 +110,63 @@ fn custom_radio_button(ui: &mut egui::Ui, selected: bool, text: &str) -> egui::R
 
 pub struct AutoClickerApp {
     clicker: AutoClicker,
-    min_cps: u32,
-    max_cps: u32,
+    left_min_cps: u32,
+    left_max_cps: u32,
+    right_min_cps: u32,
+    right_max_cps: u32,
     click_mode: ClickMode,
+    pattern: String,
+    key_combo: String,
+    type_text: String,
+    type_from_clipboard: bool,
+    type_min_delay_ms: u32,
+    type_max_delay_ms: u32,
+    newline: Newline,
+    scroll_direction: ScrollDirection,
+    scroll_notches: u32,
+    burst_clicks: u32,
+    burst_gap_ms: u32,
+    click_modifiers: Vec<Key>,
+    click_target: ClickTarget,
+    target_x: i32,
+    target_y: i32,
+    restore_pointer: bool,
+    picking: Option<(&'static str, Instant)>,
+    list_loops: u32,
+    anchor_window: String,
+    // Last anchor window lookup and when it ran, the lookup walks every window
+    anchor_lookup: Option<(Instant, Option<(i32, i32)>)>,
+    region: (i32, i32, i32, i32),
+    region_min_distance: u32,
+    drawing_region: bool,
+    region_drag_start: Option<egui::Pos2>,
+    grid_rows: u32,
+    grid_columns: u32,
+    grid_order: GridOrder,
+    grid_delay_ms: u32,
+    motion_path: MotionPath,
+    motion_duration_ms: u32,
+    motion_rate: u32,
+    drag_points: [(i32, i32); 2],
+    drag_delay_ms: u32,
+    macro_speed: u32,
+    macro_loops: u32,
+    macro_relative: bool,
+    macro_stepping: bool,
+    macro_editor: MacroEditor,
+    macro_path: String,
+    macro_file_status: Result<String, String>,
+    list_script_path: String,
+    list_script_status: Result<String, String>,
+    script: String,
+    script_path: String,
+    script_file_status: Result<String, String>,
+    activation: Activation,
+    activation_clicks: u32,
+    left_trigger_key: Option<Key>,
+    right_trigger_key: Option<Key>,
+    left_trigger_button: MouseButton,
+    right_trigger_button: MouseButton,
     static_min_cps: u32,
     static_max_cps: u32,
 }
@@ -102,21 +175,988 @@ impl Default for AutoClickerApp {
     fn default() -> Self {
         let app = Self {
             clicker: AutoClicker::new(),
-            min_cps: 5,
-            max_cps: 25,
+            left_min_cps: 5,
+            left_max_cps: 25,
+            right_min_cps: 5,
+            right_max_cps: 25,
             click_mode: ClickMode::Left,
+            pattern: "LR".to_string(),
+            key_combo: "Space".to_string(),
+            type_text: String::new(),
+            type_from_clipboard: false,
+            type_min_delay_ms: 30,
+            type_max_delay_ms: 80,
+            newline: Newline::Enter,
+            scroll_direction: ScrollDirection::Down,
+            scroll_notches: 1,
+            burst_clicks: 1,
+            burst_gap_ms: 50,
+            click_modifiers: Vec::new(),
+            click_target: ClickTarget::Cursor,
+            target_x: 0,
+            target_y: 0,
+            restore_pointer: true,
+            picking: None,
+            list_loops: 0,
+            anchor_window: String::new(),
+            anchor_lookup: None,
+            region: (0, 0, 200, 200),
+            region_min_distance: 0,
+            drawing_region: false,
+            region_drag_start: None,
+            grid_rows: 3,
+            grid_columns: 3,
+            grid_order: GridOrder::Rows,
+            grid_delay_ms: 200,
+            motion_path: MotionPath::Teleport,
+            motion_duration_ms: 200,
+            motion_rate: 60,
+            drag_points: [(0, 0), (100, 0)],
+            drag_delay_ms: 500,
+            macro_speed: 100,
+            macro_loops: 1,
+            macro_relative: false,
+            macro_stepping: false,
+            macro_editor: MacroEditor::default(),
+            macro_path: String::from("macro.txt"),
+            macro_file_status: Ok(String::new()),
+            list_script_path: String::from("clicks.sh"),
+            list_script_status: Ok(String::new()),
+            script: EXAMPLE_SCRIPT.to_string(),
+            script_path: String::from("script.lua"),
+            script_file_status: Ok(String::new()),
+            activation: Activation::Hold,
+            activation_clicks: 10,
+            left_trigger_key: None,
+            right_trigger_key: None,
+            left_trigger_button: MouseButton::Left,
+            right_trigger_button: MouseButton::Right,
             static_min_cps: 5,
             static_max_cps: 25,
         };
         
         // Initialize the clicker with our default values
-        app.clicker.set_min_cps(app.min_cps);
-        app.clicker.set_max_cps(app.max_cps);
+        app.clicker.set_min_cps(MouseButton::Left, app.left_min_cps);
+        app.clicker.set_max_cps(MouseButton::Left, app.left_max_cps);
+        app.clicker.set_min_cps(MouseButton::Right, app.right_min_cps);
+        app.clicker.set_max_cps(MouseButton::Right, app.right_max_cps);
         
         app
     }
 }
 
+impl AutoClickerApp {
+    fn cps_sliders(&mut self, ui: &mut egui::Ui, button: MouseButton, prefix: &str) {
+        let (min_cps, max_cps) = match button {
+            MouseButton::Right => (&mut self.right_min_cps, &mut self.right_max_cps),
+            _ => (&mut self.left_min_cps, &mut self.left_max_cps),
+        };
+
+        ui.label(format!("{}Min CPS:", prefix));
+        if custom_slider(ui, min_cps, self.static_min_cps..=*max_cps-1) {
+            self.clicker.set_min_cps(button, *min_cps);
+        }
+        
+        ui.add_space(5.0);
+        
+        ui.label(format!("{}Max CPS:", prefix));
+        if custom_slider(ui, max_cps, *min_cps+1..=self.static_max_cps) {
+            self.clicker.set_max_cps(button, *max_cps);
+        }
+    }
+
+    fn mode_button(&mut self, ui: &mut egui::Ui, mode: ClickMode, text: &str) {
+        if custom_radio_button(ui, self.click_mode == mode, text).clicked() {
+            self.clicker.set_click_mode(mode.clone());
+            self.click_mode = mode;
+        }
+    }
+
+    fn pattern_editor(&mut self, ui: &mut egui::Ui) {
+        ui.label("Pattern (L/R/M click, - rest):");
+        ui.text_edit_singleline(&mut self.pattern);
+
+        // Only hand valid patterns to the clicker, the last good one keeps playing
+        match parse_pattern(&self.pattern) {
+            Ok(steps) => {
+                self.clicker.set_pattern(&self.pattern);
+                ui.colored_label(egui::Color32::from_rgb(150, 150, 150), format!("{} steps", steps.len()));
+            }
+            Err(error) => {
+                ui.colored_label(egui::Color32::from_rgb(220, 80, 80), error);
+            }
+        }
+    }
+
+    fn key_combo_editor(&mut self, ui: &mut egui::Ui) {
+        ui.label("Key combo (e.g. ctrl+shift+a):");
+        ui.text_edit_singleline(&mut self.key_combo);
+
+        match parse_key_combo(&self.key_combo) {
+            Ok(keys) => {
+                self.clicker.set_key_combo(&self.key_combo);
+                let names = keys.iter().map(|key| key.name()).collect::<Vec<_>>().join(" + ");
+                ui.colored_label(egui::Color32::from_rgb(150, 150, 150), names);
+            }
+            Err(error) => {
+                ui.colored_label(egui::Color32::from_rgb(220, 80, 80), error);
+            }
+        }
+    }
+
+    fn type_settings(&mut self, ui: &mut egui::Ui) {
+        if ui.checkbox(&mut self.type_from_clipboard, "Type clipboard contents").changed() {
+            self.clicker.set_type_from_clipboard(self.type_from_clipboard);
+        }
+
+        if !self.type_from_clipboard {
+            ui.label("Text to type:");
+            if ui.text_edit_multiline(&mut self.type_text).changed() {
+                self.clicker.set_type_text(&self.type_text);
+            }
+        }
+
+        ui.add_space(5.0);
+
+        ui.label(format!("Min delay per character: {}ms", self.type_min_delay_ms));
+        if custom_slider(ui, &mut self.type_min_delay_ms, 0..=self.type_max_delay_ms) {
+            self.clicker.set_type_delay_ms(self.type_min_delay_ms, self.type_max_delay_ms);
+        }
+
+        ui.label(format!("Max delay per character: {}ms", self.type_max_delay_ms));
+        if custom_slider(ui, &mut self.type_max_delay_ms, self.type_min_delay_ms..=500) {
+            self.clicker.set_type_delay_ms(self.type_min_delay_ms, self.type_max_delay_ms);
+        }
+
+        ui.add_space(5.0);
+
+        ui.label("Line breaks:");
+        ui.horizontal_wrapped(|ui| {
+            for (newline, text) in [
+                (Newline::Enter, "Enter"),
+                (Newline::ShiftEnter, "Shift+Enter"),
+                (Newline::Skip, "Skip"),
+            ] {
+                if custom_radio_button(ui, self.newline == newline, text).clicked() {
+                    self.newline = newline;
+                    self.clicker.set_newline(newline);
+                }
+            }
+        });
+    }
+
+    fn scroll_settings(&mut self, ui: &mut egui::Ui) {
+        ui.label("Scroll Direction:");
+        ui.horizontal_wrapped(|ui| {
+            for (direction, text) in [
+                (ScrollDirection::Up, "Up"),
+                (ScrollDirection::Down, "Down"),
+                (ScrollDirection::Left, "Left"),
+                (ScrollDirection::Right, "Right"),
+            ] {
+                if custom_radio_button(ui, self.scroll_direction == direction, text).clicked() {
+                    self.scroll_direction = direction;
+                    self.clicker.set_scroll_direction(direction);
+                }
+            }
+        });
+
+        ui.add_space(5.0);
+
+        ui.label(format!("Notches per scroll: {}", self.scroll_notches));
+        if custom_slider(ui, &mut self.scroll_notches, 1..=10) {
+            self.clicker.set_scroll_notches(self.scroll_notches);
+        }
+    }
+
+    fn click_unit_settings(&mut self, ui: &mut egui::Ui) {
+        ui.label("Click Unit:");
+        ui.horizontal(|ui| {
+            for (clicks, text) in [(1, "Single"), (2, "Double"), (3, "Triple")] {
+                if custom_radio_button(ui, self.burst_clicks == clicks, text).clicked() {
+                    self.burst_clicks = clicks;
+                    self.clicker.set_burst_clicks(clicks);
+                }
+                ui.add_space(5.0);
+            }
+        });
+
+        if self.burst_clicks > 1 {
+            // Keep the gap under the system double-click time or the burst splits up
+            let max_gap = (double_click_time().as_millis() as u32).saturating_sub(10).max(11);
+            ui.add_space(5.0);
+            ui.label(format!("Gap between presses: {}ms", self.burst_gap_ms));
+            if custom_slider(ui, &mut self.burst_gap_ms, 10..=max_gap) {
+                self.clicker.set_burst_gap_ms(self.burst_gap_ms);
+            }
+        }
+
+        ui.add_space(5.0);
+        self.modifier_settings(ui);
+    }
+
+    fn macro_settings(&mut self, ui: &mut egui::Ui) {
+        let recording = self.clicker.is_recording();
+        ui.horizontal(|ui| {
+            let text = if recording { "Stop recording" } else { "Record" };
+            if ui.button(text).clicked() {
+                self.clicker.toggle_recording();
+            }
+            if recording {
+                ui.colored_label(egui::Color32::from_rgb(220, 80, 80), "Recording...");
+            }
+        });
+        ui.colored_label(egui::Color32::from_rgb(150, 150, 150), "F8 also starts and stops recording");
+        if ui.button("Open editor").clicked() {
+            self.macro_editor.open = true;
+        }
+
+        ui.add_space(5.0);
+
+        let mut steps = self.clicker.macro_steps();
+        ui.label(format!("Macro: {} steps", steps.len()));
+
+        let mut remove = None;
+        egui::ScrollArea::vertical().id_source("macro_steps").max_height(150.0).show(ui, |ui| {
+            for (i, step) in steps.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    if ui.small_button("🗙").clicked() {
+                        remove = Some(i);
+                    }
+                    ui.monospace(step.to_string());
+                });
+            }
+        });
+        if let Some(i) = remove {
+            steps.remove(i);
+            self.clicker.set_macro_steps(&steps);
+        }
+
+        ui.add_space(5.0);
+
+        self.macro_file_editor(ui);
+
+        ui.add_space(5.0);
+
+        ui.label(format!("Playback speed: {}%", self.macro_speed));
+        if custom_slider(ui, &mut self.macro_speed, 25..=400) {
+            self.clicker.set_macro_speed(self.macro_speed);
+        }
+
+        if self.macro_loops == 0 {
+            ui.label("Loops: forever");
+        } else {
+            ui.label(format!("Loops: {}", self.macro_loops));
+        }
+        if custom_slider(ui, &mut self.macro_loops, 0..=100) {
+            self.clicker.set_macro_loops(self.macro_loops);
+        }
+
+        if ui.checkbox(&mut self.macro_relative, "Moves relative to pointer at start").changed() {
+            self.clicker.set_macro_relative(self.macro_relative);
+        }
+
+        if ui.checkbox(&mut self.macro_stepping, "Single step (debug)").changed() {
+            self.clicker.set_macro_stepping(self.macro_stepping);
+        }
+
+        if self.clicker.is_running() && !steps.is_empty() {
+            let (step, round) = self.clicker.macro_progress();
+            ui.colored_label(
+                egui::Color32::from_rgb(150, 150, 150),
+                format!("Playing step {}/{}, loop {}", step, steps.len(), round),
+            );
+
+            if self.macro_stepping {
+                let (next, vars) = self.clicker.macro_debug();
+                ui.horizontal(|ui| {
+                    if ui.button("Next step").clicked() {
+                        self.clicker.step_macro();
+                    }
+                    ui.monospace(next);
+                });
+                for (name, value) in vars {
+                    ui.monospace(format!("{} = {}", name, value));
+                }
+                ui.colored_label(
+                    egui::Color32::from_rgb(150, 150, 150),
+                    "Steps run once another window has the focus",
+                );
+            }
+        }
+    }
+
+    fn macro_file_editor(&mut self, ui: &mut egui::Ui) {
+        ui.label("Macro file:");
+        ui.text_edit_singleline(&mut self.macro_path);
+        ui.horizontal(|ui| {
+            if ui.button("Save").clicked() {
+                let text = to_text(&self.clicker.macro_steps());
+                self.macro_file_status = std::fs::write(&self.macro_path, text)
+                    .map(|_| format!("Saved {}", self.macro_path))
+                    .map_err(|error| error.to_string());
+            }
+            if ui.button("Load").clicked() {
+                self.macro_file_status = match std::fs::read_to_string(&self.macro_path) {
+                    // A broken file leaves the current macro alone
+                    Ok(text) => match parse_macro(&text) {
+                        Ok(steps) => {
+                            self.clicker.set_macro_steps(&steps);
+                            Ok(format!("Loaded {} steps", steps.len()))
+                        }
+                        Err(error) => Err(error.to_string()),
+                    },
+                    Err(error) => Err(error.to_string()),
+                };
+            }
+        });
+
+        // Shell scripts sit next to the macro file, as the same name ending in .sh
+        let script = std::path::Path::new(&self.macro_path).with_extension("sh");
+        ui.horizontal(|ui| {
+            if ui.button("Export xdotool").clicked() {
+                self.macro_file_status = export_macro(&self.clicker.macro_steps())
+                    .and_then(|text| write_script(&script, &text).map_err(|error| error.to_string()))
+                    .map(|_| format!("Exported {}", script.display()));
+            }
+            if ui.button("Import xdotool").clicked() {
+                self.macro_file_status = match std::fs::read_to_string(&script) {
+                    Ok(text) => match import_macro(&text) {
+                        Ok(steps) => {
+                            self.clicker.set_macro_steps(&steps);
+                            Ok(format!("Imported {} steps", steps.len()))
+                        }
+                        Err(error) => Err(error.to_string()),
+                    },
+                    Err(error) => Err(format!("{}: {}", script.display(), error)),
+                };
+            }
+        });
+
+        file_status(ui, &self.macro_file_status);
+    }
+
+    fn script_settings(&mut self, ui: &mut egui::Ui) {
+        ui.label("Lua script (on_start, on_tick, on_window_change):");
+        ui.add(
+            egui::TextEdit::multiline(&mut self.script)
+                .code_editor()
+                .desired_rows(8)
+                .desired_width(f32::INFINITY),
+        );
+        ui.colored_label(
+            egui::Color32::from_rgb(150, 150, 150),
+            "click(btn, n) move(x, y) sleep(ms) pixel(x, y) focused_window() key(combo) print(...)",
+        );
+
+        // Edits only reach the clicker on Apply, so a half typed script never runs
+        ui.horizontal(|ui| {
+            if ui.button("Apply").clicked() {
+                self.clicker.set_script(&self.script);
+            }
+            if ui.button("Example").clicked() {
+                self.script = EXAMPLE_SCRIPT.to_string();
+            }
+        });
+
+        ui.label("Script file:");
+        ui.text_edit_singleline(&mut self.script_path);
+        ui.horizontal(|ui| {
+            if ui.button("Save").clicked() {
+                self.script_file_status = std::fs::write(&self.script_path, &self.script)
+                    .map(|_| format!("Saved {}", self.script_path))
+                    .map_err(|error| error.to_string());
+            }
+            if ui.button("Load").clicked() {
+                self.script_file_status = match std::fs::read_to_string(&self.script_path) {
+                    Ok(text) => {
+                        self.script = text;
+                        self.clicker.set_script(&self.script);
+                        Ok(format!("Loaded {}", self.script_path))
+                    }
+                    Err(error) => Err(error.to_string()),
+                };
+            }
+        });
+        file_status(ui, &self.script_file_status);
+
+        let error = self.clicker.script_error();
+        if !error.is_empty() {
+            ui.colored_label(egui::Color32::from_rgb(220, 80, 80), error);
+        }
+
+        // Most recent printed lines last
+        let log = self.clicker.script_log();
+        for line in log.iter().skip(log.len().saturating_sub(5)) {
+            ui.colored_label(egui::Color32::from_rgb(150, 150, 150), line);
+        }
+    }
+
+    fn modifier_settings(&mut self, ui: &mut egui::Ui) {
+        ui.label("Hold while clicking:");
+        ui.horizontal(|ui| {
+            for name in ["Shift", "Ctrl", "Alt"] {
+                let key = Key::from_name(name).unwrap();
+                let mut held = self.click_modifiers.contains(&key);
+                if ui.checkbox(&mut held, name).changed() {
+                    if held {
+                        self.click_modifiers.push(key);
+                    } else {
+                        self.click_modifiers.retain(|&modifier| modifier != key);
+                    }
+                    self.clicker.set_click_modifiers(&self.click_modifiers);
+                }
+            }
+        });
+    }
+
+    fn click_target_settings(&mut self, ui: &mut egui::Ui) {
+        ui.label("Click At:");
+        ui.horizontal_wrapped(|ui| {
+            for (target, text) in [
+                (ClickTarget::Cursor, "Cursor"),
+                (ClickTarget::Fixed, "Fixed point"),
+                (ClickTarget::Region, "Random in region"),
+            ] {
+                if custom_radio_button(ui, self.click_target == target, text).clicked() {
+                    self.click_target = target;
+                    self.clicker.set_click_target(target);
+                }
+                ui.add_space(5.0);
+            }
+        });
+
+        ui.add_space(5.0);
+        match self.click_target {
+            ClickTarget::Cursor => return,
+            ClickTarget::Fixed => self.fixed_point_editor(ui),
+            ClickTarget::Region => {
+                self.region_editor(ui);
+                ui.add_space(5.0);
+                ui.label(format!("Min distance from last click: {}px", self.region_min_distance));
+                if custom_slider(ui, &mut self.region_min_distance, 0..=500) {
+                    self.clicker.set_region_min_distance(self.region_min_distance);
+                }
+            }
+        }
+
+        if ui.checkbox(&mut self.restore_pointer, "Return pointer after clicking").changed() {
+            self.clicker.set_restore_pointer(self.restore_pointer);
+        }
+
+        ui.add_space(5.0);
+        self.motion_settings(ui);
+
+        ui.add_space(5.0);
+        self.anchor_window_editor(ui);
+    }
+
+    fn fixed_point_editor(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("X:");
+            let x_changed = ui.add(egui::DragValue::new(&mut self.target_x)).changed();
+            ui.label("Y:");
+            let y_changed = ui.add(egui::DragValue::new(&mut self.target_y)).changed();
+            if x_changed || y_changed {
+                self.clicker.set_target_position(self.target_x, self.target_y);
+            }
+        });
+
+        if let Some((x, y)) = self.pick_button(ui, "target", "Pick in 3s") {
+            (self.target_x, self.target_y) = (x, y);
+            self.clicker.set_target_position(x, y);
+        }
+    }
+
+    // Gives the user a few seconds to move the pointer over a target, then returns its position.
+    // Only one picker counts down at a time.
+    fn pick_button(&mut self, ui: &mut egui::Ui, id: &'static str, text: &str) -> Option<(i32, i32)> {
+        match self.picking {
+            Some((picking, started)) if picking == id && started.elapsed() >= Duration::from_secs(3) => {
+                self.picking = None;
+                self.clicker.capture_position()
+            }
+            Some((picking, started)) if picking == id => {
+                let left = 3 - started.elapsed().as_secs();
+                ui.label(format!("Hover the target... {}s", left));
+                None
+            }
+            _ => {
+                if ui.add_enabled(self.picking.is_none(), egui::Button::new(text)).clicked() {
+                    self.picking = Some((id, Instant::now()));
+                }
+                None
+            }
+        }
+    }
+
+    fn drag_settings(&mut self, ui: &mut egui::Ui) {
+        let mut changed = false;
+        for (i, (text, id)) in [("From", "drag_from"), ("To", "drag_to")].into_iter().enumerate() {
+            let (x, y) = &mut self.drag_points[i];
+            ui.horizontal(|ui| {
+                ui.label(format!("{} X:", text));
+                changed |= ui.add(egui::DragValue::new(x)).changed();
+                ui.label("Y:");
+                changed |= ui.add(egui::DragValue::new(y)).changed();
+            });
+            if let Some(position) = self.pick_button(ui, id, &format!("Pick {} in 3s", text.to_lowercase())) {
+                self.drag_points[i] = position;
+                changed = true;
+            }
+        }
+        if changed {
+            self.clicker.set_drag(self.drag_points[0], self.drag_points[1]);
+        }
+
+        ui.add_space(5.0);
+
+        ui.label(format!("Delay between drags: {}ms", self.drag_delay_ms));
+        if custom_slider(ui, &mut self.drag_delay_ms, 0..=5000) {
+            self.clicker.set_drag_delay_ms(self.drag_delay_ms);
+        }
+
+        ui.add_space(5.0);
+        self.modifier_settings(ui);
+
+        ui.add_space(5.0);
+        self.sequence_settings(ui);
+    }
+
+    fn region_editor(&mut self, ui: &mut egui::Ui) {
+        let (x, y, width, height) = &mut self.region;
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.label("X:");
+            changed |= ui.add(egui::DragValue::new(x)).changed();
+            ui.label("Y:");
+            changed |= ui.add(egui::DragValue::new(y)).changed();
+        });
+        ui.horizontal(|ui| {
+            ui.label("W:");
+            changed |= ui.add(egui::DragValue::new(width).clamp_range(1..=10000)).changed();
+            ui.label("H:");
+            changed |= ui.add(egui::DragValue::new(height).clamp_range(1..=10000)).changed();
+        });
+        if changed {
+            self.clicker.set_region(*x, *y, *width, *height);
+        }
+
+        if ui.button("Draw region").clicked() {
+            self.drawing_region = true;
+            self.region_drag_start = None;
+        }
+    }
+
+    // Fullscreen see-through window to drag out the region with a rubber band
+    fn region_overlay(&mut self, ctx: &egui::Context) {
+        let builder = egui::ViewportBuilder::default()
+            .with_title("Draw region")
+            .with_fullscreen(true)
+            .with_decorations(false)
+            .with_transparent(true)
+            .with_always_on_top();
+
+        ctx.show_viewport_immediate(egui::ViewportId::from_hash_of("region_overlay"), builder, |ctx, _class| {
+            let frame = egui::Frame::none().fill(egui::Color32::from_black_alpha(80));
+            egui::CentralPanel::default().frame(frame).show(ctx, |ui| {
+                ui.colored_label(egui::Color32::WHITE, "Drag out the region, Escape cancels");
+                let response = ui.interact(ui.max_rect(), ui.id().with("region"), egui::Sense::drag());
+
+                if response.drag_started() {
+                    self.region_drag_start = response.interact_pointer_pos();
+                }
+
+                let current = ctx.pointer_latest_pos();
+                if let (Some(start), Some(current)) = (self.region_drag_start, current) {
+                    let rect = egui::Rect::from_two_pos(start, current);
+                    ui.painter().rect(
+                        rect,
+                        0.0,
+                        egui::Color32::from_white_alpha(30),
+                        egui::Stroke::new(2.0, egui::Color32::from_rgb(80, 160, 255)),
+                    );
+
+                    if response.drag_released() {
+                        // Window points to physical screen pixels
+                        let origin = ctx.input(|i| i.viewport().inner_rect).map_or(egui::Pos2::ZERO, |r| r.min);
+                        let scale = ctx.pixels_per_point();
+                        let min = (origin + rect.min.to_vec2()) * scale;
+                        let max = (origin + rect.max.to_vec2()) * scale;
+
+                        if let Some((origin_x, origin_y)) = self.clicker.anchor_origin() {
+                            self.region = (
+                                min.x.round() as i32 - origin_x,
+                                min.y.round() as i32 - origin_y,
+                                ((max.x - min.x).round() as i32).max(1),
+                                ((max.y - min.y).round() as i32).max(1),
+                            );
+                            let (x, y, width, height) = self.region;
+                            self.clicker.set_region(x, y, width, height);
+                        }
+                        self.drawing_region = false;
+                    }
+                }
+            });
+
+            if ctx.input(|i| i.key_pressed(egui::Key::Escape) || i.viewport().close_requested()) {
+                self.drawing_region = false;
+            }
+        });
+    }
+
+    fn click_list_editor(&mut self, ui: &mut egui::Ui) {
+        // The list lives in the clicker so the capture hotkey can append to it. Edits are
+        // applied to it rather than writing this copy back, which would drop new captures.
+        let mut points = self.clicker.click_points();
+        let mut edited = None;
+        let mut remove = None;
+        let mut swap = None;
+        let mut add = false;
+        let mut clear = false;
+
+        ui.label("Click list (F7 adds the pointer position):");
+        egui::Grid::new("click_list").spacing([4.0, 4.0]).show(ui, |ui| {
+            ui.label("X");
+            ui.label("Y");
+            ui.label("Btn");
+            ui.label("Clicks");
+            ui.label("Delay");
+            ui.end_row();
+
+            let count = points.len();
+            for (i, point) in points.iter_mut().enumerate() {
+                let mut changed = ui.add(egui::DragValue::new(&mut point.x)).changed();
+                changed |= ui.add(egui::DragValue::new(&mut point.y)).changed();
+
+                // Click to cycle through the buttons
+                let button_text = match point.button {
+                    MouseButton::Right => "R",
+                    MouseButton::Middle => "M",
+                    _ => "L",
+                };
+                if ui.button(button_text).clicked() {
+                    point.button = match point.button {
+                        MouseButton::Left => MouseButton::Right,
+                        MouseButton::Right => MouseButton::Middle,
+                        _ => MouseButton::Left,
+                    };
+                    changed = true;
+                }
+
+                changed |= ui.add(egui::DragValue::new(&mut point.clicks).clamp_range(1..=10)).changed();
+                changed |= ui
+                    .add(egui::DragValue::new(&mut point.delay_ms).clamp_range(0..=60000).suffix("ms"))
+                    .changed();
+
+                if ui.add_enabled(i > 0, egui::Button::new("⬆")).clicked() {
+                    swap = Some((i - 1, i));
+                }
+                if ui.add_enabled(i + 1 < count, egui::Button::new("⬇")).clicked() {
+                    swap = Some((i, i + 1));
+                }
+                if ui.button("🗙").clicked() {
+                    remove = Some(i);
+                }
+
+                // Turns the point into a drag, with its end edited on an extra row below
+                if ui.selectable_label(point.drag_to.is_some(), "Drag").clicked() {
+                    point.drag_to = match point.drag_to {
+                        Some(_) => None,
+                        None => Some((point.x, point.y)),
+                    };
+                    changed = true;
+                }
+                ui.end_row();
+
+                if let Some((to_x, to_y)) = &mut point.drag_to {
+                    changed |= ui.add(egui::DragValue::new(to_x)).changed();
+                    changed |= ui.add(egui::DragValue::new(to_y)).changed();
+                    ui.label("end");
+                    ui.end_row();
+                }
+
+                if changed {
+                    edited = Some((i, *point));
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            add = ui.button("Add point").clicked();
+            clear = ui.button("Clear").clicked();
+        });
+
+        // Captures only ever append, so the row indices still point at the same points
+        if edited.is_some() || swap.is_some() || remove.is_some() || add || clear {
+            self.clicker.edit_click_points(|points| {
+                if let Some((i, point)) = edited {
+                    if let Some(slot) = points.get_mut(i) {
+                        *slot = point;
+                    }
+                }
+                if let Some((a, b)) = swap {
+                    points.swap(a, b);
+                }
+                if let Some(i) = remove {
+                    points.remove(i);
+                }
+                if add {
+                    let (x, y) = points.last().map_or((0, 0), |point| (point.x, point.y));
+                    points.push(ClickPoint::at(x, y));
+                }
+                if clear {
+                    points.clear();
+                }
+            });
+            points = self.clicker.click_points();
+        }
+
+        ui.add_space(5.0);
+        self.sequence_settings(ui);
+
+        ui.add_space(5.0);
+        ui.label("xdotool script:");
+        ui.text_edit_singleline(&mut self.list_script_path);
+        if ui.add_enabled(!points.is_empty(), egui::Button::new("Export xdotool")).clicked() {
+            let script = export_click_list(&points, self.list_loops, &self.anchor_window, &self.click_modifiers);
+            self.list_script_status = write_script(std::path::Path::new(&self.list_script_path), &script)
+                .map(|_| format!("Exported {}", self.list_script_path))
+                .map_err(|error| error.to_string());
+        }
+        file_status(ui, &self.list_script_status);
+    }
+
+    fn grid_settings(&mut self, ui: &mut egui::Ui) {
+        ui.label("Region to sweep:");
+        self.region_editor(ui);
+
+        ui.add_space(5.0);
+
+        ui.label(format!("Rows: {}", self.grid_rows));
+        if custom_slider(ui, &mut self.grid_rows, 1..=20) {
+            self.clicker.set_grid_size(self.grid_rows, self.grid_columns);
+        }
+        ui.label(format!("Columns: {}", self.grid_columns));
+        if custom_slider(ui, &mut self.grid_columns, 1..=20) {
+            self.clicker.set_grid_size(self.grid_rows, self.grid_columns);
+        }
+
+        ui.add_space(5.0);
+
+        ui.label("Order:");
+        ui.horizontal_wrapped(|ui| {
+            for (order, text) in [
+                (GridOrder::Rows, "Rows"),
+                (GridOrder::Columns, "Columns"),
+                (GridOrder::Serpentine, "Serpentine"),
+                (GridOrder::Shuffled, "Shuffled"),
+            ] {
+                if custom_radio_button(ui, self.grid_order == order, text).clicked() {
+                    self.grid_order = order;
+                    self.clicker.set_grid_order(order);
+                }
+            }
+        });
+
+        ui.add_space(5.0);
+
+        ui.label(format!("Delay per cell: {}ms", self.grid_delay_ms));
+        if custom_slider(ui, &mut self.grid_delay_ms, 0..=2000) {
+            self.clicker.set_grid_delay_ms(self.grid_delay_ms);
+        }
+
+        ui.add_space(5.0);
+        self.sequence_settings(ui);
+    }
+
+    // Shared by the click list and grid sweep
+    fn sequence_settings(&mut self, ui: &mut egui::Ui) {
+        if self.list_loops == 0 {
+            ui.label("Loops: forever");
+        } else {
+            ui.label(format!("Loops: {}", self.list_loops));
+        }
+        if custom_slider(ui, &mut self.list_loops, 0..=100) {
+            self.clicker.set_list_loops(self.list_loops);
+        }
+
+        if ui.checkbox(&mut self.restore_pointer, "Return pointer after clicking").changed() {
+            self.clicker.set_restore_pointer(self.restore_pointer);
+        }
+
+        ui.add_space(5.0);
+        self.motion_settings(ui);
+
+        ui.add_space(5.0);
+        self.anchor_window_editor(ui);
+    }
+
+    fn motion_settings(&mut self, ui: &mut egui::Ui) {
+        ui.label("Pointer motion:");
+        ui.horizontal_wrapped(|ui| {
+            for (path, text) in [
+                (MotionPath::Teleport, "Teleport"),
+                (MotionPath::Linear, "Linear"),
+                (MotionPath::Eased, "Eased"),
+                (MotionPath::Bezier, "Curved"),
+            ] {
+                if custom_radio_button(ui, self.motion_path == path, text).clicked() {
+                    self.motion_path = path;
+                    self.clicker.set_motion_path(path);
+                }
+            }
+        });
+
+        if self.motion_path == MotionPath::Teleport {
+            return;
+        }
+
+        ui.label(format!("Move duration: {}ms", self.motion_duration_ms));
+        if custom_slider(ui, &mut self.motion_duration_ms, 10..=2000) {
+            self.clicker.set_motion_timing(self.motion_duration_ms, self.motion_rate);
+        }
+        ui.label(format!("Steps per second: {}", self.motion_rate));
+        if custom_slider(ui, &mut self.motion_rate, 10..=240) {
+            self.clicker.set_motion_timing(self.motion_duration_ms, self.motion_rate);
+        }
+    }
+
+    fn anchor_window_editor(&mut self, ui: &mut egui::Ui) {
+        ui.label("Relative to window (title or class):");
+        if ui.text_edit_singleline(&mut self.anchor_window).changed() {
+            self.clicker.set_anchor_window(&self.anchor_window);
+            self.anchor_lookup = None;
+        }
+
+        if self.anchor_window.trim().is_empty() {
+            ui.colored_label(egui::Color32::from_rgb(150, 150, 150), "Using screen coordinates");
+        } else {
+            // Looked up again every second rather than on every repaint
+            if self.anchor_lookup.is_none_or(|(checked, _)| checked.elapsed() >= Duration::from_secs(1)) {
+                self.anchor_lookup = Some((Instant::now(), self.clicker.anchor_origin()));
+            }
+            match self.anchor_lookup.and_then(|(_, origin)| origin) {
+                Some((x, y)) => {
+                    ui.colored_label(egui::Color32::from_rgb(150, 150, 150), format!("Window at {}, {}", x, y));
+                }
+                None => {
+                    ui.colored_label(egui::Color32::from_rgb(220, 80, 80), "No matching window, clicks paused");
+                }
+            }
+        }
+    }
+
+    fn activation_settings(&mut self, ui: &mut egui::Ui) {
+        ui.label("Activation:");
+        ui.add_space(5.0);
+        ui.horizontal_wrapped(|ui| {
+            for (activation, text) in [
+                (Activation::Hold, "Hold"),
+                (Activation::Latch, "Latch"),
+                (Activation::AlwaysOn, "Always"),
+                (Activation::Count, "Count"),
+            ] {
+                if custom_radio_button(ui, self.activation == activation, text).clicked() {
+                    self.activation = activation;
+                    self.clicker.set_activation(activation);
+                }
+            }
+        });
+
+        if self.activation == Activation::Latch {
+            ui.add_space(5.0);
+            ui.label(format!("Latched: {}", self.latched_text()));
+        }
+
+        if self.activation != Activation::AlwaysOn {
+            ui.add_space(5.0);
+            self.trigger_button_picker(ui, MouseButton::Left, "Left button:");
+            self.trigger_button_picker(ui, MouseButton::Right, "Right button:");
+            self.trigger_key_picker(ui, MouseButton::Left, "Left key:");
+            self.trigger_key_picker(ui, MouseButton::Right, "Right key:");
+        }
+
+        if self.activation == Activation::Count {
+            ui.add_space(5.0);
+            ui.label(format!("Clicks per activation: {}", self.activation_clicks));
+            if custom_slider(ui, &mut self.activation_clicks, 1..=100) {
+                self.clicker.set_activation_clicks(self.activation_clicks);
+            }
+            ui.label(format!("Fired: {}", self.clicker.clicks_fired()));
+        }
+    }
+
+    // Moves a stream's trigger to the middle or a side button so the primary button stays free
+    fn trigger_button_picker(&mut self, ui: &mut egui::Ui, button: MouseButton, text: &str) {
+        let selected = match button {
+            MouseButton::Right => &mut self.right_trigger_button,
+            _ => &mut self.left_trigger_button,
+        };
+        let old_button = *selected;
+
+        ui.horizontal(|ui| {
+            ui.label(text);
+            egui::ComboBox::from_id_source(text)
+                .selected_text(format!("{:?}", selected))
+                .show_ui(ui, |ui| {
+                    for trigger in [button, MouseButton::Middle, MouseButton::X1, MouseButton::X2] {
+                        ui.selectable_value(selected, trigger, format!("{:?}", trigger));
+                    }
+                });
+        });
+
+        if *selected != old_button {
+            self.clicker.set_trigger_button(button, *selected);
+        }
+    }
+
+    // Lets a held key stand in for the physical button, F6-F8 are left out as they are hotkeys
+    fn trigger_key_picker(&mut self, ui: &mut egui::Ui, button: MouseButton, text: &str) {
+        let selected = match button {
+            MouseButton::Right => &mut self.right_trigger_key,
+            _ => &mut self.left_trigger_key,
+        };
+        let old_key = *selected;
+
+        ui.horizontal(|ui| {
+            ui.label(text);
+            egui::ComboBox::from_id_source(text)
+                .selected_text(selected.map_or("None", |key| key.name()))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(selected, None, "None");
+                    for key in Key::all().filter(|key| !["F6", "F7", "F8"].contains(&key.name())) {
+                        ui.selectable_value(selected, Some(key), key.name());
+                    }
+                });
+        });
+
+        if *selected != old_key {
+            self.clicker.set_trigger_key(button, *selected);
+        }
+    }
+
+    fn latched_text(&self) -> String {
+        let state = |button| if self.clicker.is_latched(button) { "On" } else { "Off" };
+        match self.click_mode {
+            ClickMode::Right => state(MouseButton::Right).to_string(),
+            ClickMode::Both => format!("L {}, R {}", state(MouseButton::Left), state(MouseButton::Right)),
+            _ => state(MouseButton::Left).to_string(),
+        }
+    }
+
+    fn cps_range_text(&self) -> String {
+        match self.click_mode {
+            ClickMode::Right => format!("{}-{}", self.right_min_cps, self.right_max_cps),
+            ClickMode::Both => format!(
+                "L {}-{}, R {}-{}",
+                self.left_min_cps, self.left_max_cps, self.right_min_cps, self.right_max_cps
+            ),
+            _ => format!("{}-{}", self.left_min_cps, self.left_max_cps),
+        }
+    }
+}
+
 impl eframe::App for AutoClickerApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // Request continuous repainting to update status
@@ -141,76 +1181,139 @@ impl eframe::App for AutoClickerApp {
         style.visuals.widgets.open.rounding = egui::Rounding::same(8.0);
         ctx.set_style(style);
 
+        if self.drawing_region {
+            self.region_overlay(ctx);
+        }
+
+        if self.macro_editor.open {
+            if let Some(step) = self.macro_editor.show(ctx, &self.clicker) {
+                self.click_mode = ClickMode::Macro;
+                self.clicker.run_macro_from(step);
+            }
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
-            ui.heading("Auto Clicker");
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                ui.heading("Auto Clicker");
             
-            ui.add_space(10.0);
+                ui.add_space(10.0);
             
-            // CPS Settings
-            ui.vertical(|ui| {
-                ui.label("Min CPS:");
-                if custom_slider(ui, &mut self.min_cps, self.static_min_cps..=self.max_cps-1) {
-                    self.clicker.set_min_cps(self.min_cps);
-                }
-                
-                ui.add_space(5.0);
-                
-                ui.label("Max CPS:");
-                if custom_slider(ui, &mut self.max_cps, self.min_cps+1..=self.static_max_cps) {
-                    self.clicker.set_max_cps(self.max_cps);
-                }
-            });
+                // CPS Settings, one range per active button
+                ui.vertical(|ui| {
+                    match self.click_mode {
+                        ClickMode::Right => self.cps_sliders(ui, MouseButton::Right, ""),
+                        ClickMode::Both => {
+                            self.cps_sliders(ui, MouseButton::Left, "Left ");
+                            ui.add_space(5.0);
+                            self.cps_sliders(ui, MouseButton::Right, "Right ");
+                        }
+                        _ => self.cps_sliders(ui, MouseButton::Left, ""),
+                    }
+                });
             
-            ui.add_space(10.0);
+                ui.add_space(10.0);
             
-            // Click Mode Selection
-            ui.vertical(|ui| {
-                ui.label("Click Mode:");
-                ui.add_space(5.0);
-                ui.horizontal(|ui| {
-                    if custom_radio_button(ui, self.click_mode == ClickMode::Left, "Left").clicked() {
-                        self.click_mode = ClickMode::Left;
-                        self.clicker.set_click_mode(ClickMode::Left);
-                    }
+                // Click Mode Selection
+                ui.vertical(|ui| {
+                    ui.label("Click Mode:");
                     ui.add_space(5.0);
-                    if custom_radio_button(ui, self.click_mode == ClickMode::Right, "Right").clicked() {
-                        self.click_mode = ClickMode::Right;
-                        self.clicker.set_click_mode(ClickMode::Right);
-                    }
+                    ui.horizontal(|ui| {
+                        self.mode_button(ui, ClickMode::Left, "Left");
+                        ui.add_space(5.0);
+                        self.mode_button(ui, ClickMode::Right, "Right");
+                        ui.add_space(5.0);
+                        self.mode_button(ui, ClickMode::Both, "Both");
+                    });
+                    ui.add_space(5.0);
+                    ui.horizontal(|ui| {
+                        self.mode_button(ui, ClickMode::Alternate, "Alternate");
+                        ui.add_space(5.0);
+                        self.mode_button(ui, ClickMode::Pattern, "Pattern");
+                        ui.add_space(5.0);
+                        self.mode_button(ui, ClickMode::Scroll, "Scroll");
+                    });
                     ui.add_space(5.0);
-                    if custom_radio_button(ui, self.click_mode == ClickMode::Both, "Both").clicked() {
-                        self.click_mode = ClickMode::Both;
-                        self.clicker.set_click_mode(ClickMode::Both);
+                    ui.horizontal(|ui| {
+                        self.mode_button(ui, ClickMode::Keys, "Keys");
+                        ui.add_space(5.0);
+                        self.mode_button(ui, ClickMode::Type, "Type");
+                        ui.add_space(5.0);
+                        self.mode_button(ui, ClickMode::List, "List");
+                        ui.add_space(5.0);
+                        self.mode_button(ui, ClickMode::Grid, "Grid");
+                    });
+                    ui.add_space(5.0);
+                    ui.horizontal(|ui| {
+                        self.mode_button(ui, ClickMode::Drag, "Drag");
+                        ui.add_space(5.0);
+                        self.mode_button(ui, ClickMode::Macro, "Macro");
+                        ui.add_space(5.0);
+                        self.mode_button(ui, ClickMode::Script, "Script");
+                    });
+
+                    ui.add_space(5.0);
+                    match self.click_mode {
+                        ClickMode::Scroll => self.scroll_settings(ui),
+                        ClickMode::Keys => self.key_combo_editor(ui),
+                        ClickMode::Type => self.type_settings(ui),
+                        ClickMode::List => self.click_list_editor(ui),
+                        ClickMode::Drag => self.drag_settings(ui),
+                        ClickMode::Macro => self.macro_settings(ui),
+                        ClickMode::Script => self.script_settings(ui),
+                        ClickMode::Grid => {
+                            self.grid_settings(ui);
+                            ui.add_space(5.0);
+                            self.click_unit_settings(ui);
+                        }
+                        ClickMode::Pattern => {
+                            self.pattern_editor(ui);
+                            ui.add_space(5.0);
+                            self.click_unit_settings(ui);
+                            ui.add_space(5.0);
+                            self.click_target_settings(ui);
+                        }
+                        _ => {
+                            self.click_unit_settings(ui);
+                            ui.add_space(5.0);
+                            self.click_target_settings(ui);
+                        }
                     }
                 });
-            });
             
-            ui.add_space(10.0);
+                ui.add_space(10.0);
+
+                // Activation Selection
+                ui.vertical(|ui| {
+                    self.activation_settings(ui);
+                });
+
+                ui.add_space(10.0);
             
-            // Toggle Button
-            let is_running = self.clicker.is_running();
-            if ui.add(egui::Button::new(if is_running { "Stop" } else { "Start" })
-                .fill(if is_running { egui::Color32::from_rgb(200, 0, 0) } else { egui::Color32::from_rgb(0, 120, 212) }))
-                .clicked() {
-                self.clicker.toggle_running();
-            }
+                // Toggle Button
+                let is_running = self.clicker.is_running();
+                if ui.add(egui::Button::new(if is_running { "Stop" } else { "Start" })
+                    .fill(if is_running { egui::Color32::from_rgb(200, 0, 0) } else { egui::Color32::from_rgb(0, 120, 212) }))
+                    .clicked() {
+                    self.clicker.toggle_running();
+                }
             
-            ui.add_space(10.0);
+                ui.add_space(10.0);
             
-            // Status
-            ui.label(format!("Status: {}", if is_running { "Running" } else { "Stopped" }));
-            ui.label(format!("Current Mode: {:?}", self.click_mode));
-            ui.label(format!("CPS Range: {}-{}", self.min_cps, self.max_cps));
+                // Status
+                ui.label(format!("Status: {}", if is_running { "Running" } else { "Stopped" }));
+                ui.label(format!("Current Mode: {:?}", self.click_mode));
+                ui.label(format!("CPS Range: {}", self.cps_range_text()));
             
-            ui.add_space(10.0);
+                ui.add_space(10.0);
             
-            // Instructions
-            ui.label("Press F6 to toggle the clicker on/off");
+                // Instructions
+                ui.label("Press F6 to toggle the clicker on/off");
             
-            ui.add_space(10.0);
+                ui.add_space(10.0);
             
-            // Credit
-            ui.label("Made by BuPyC12");
+                // Credit
+                ui.label("Made by BuPyC12");
+            });
         });
     }
 } 
\ No newline at end of file
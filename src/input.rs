@@ -0,0 +1,746 @@
+use std::thread;
+use std::time::Duration;
+
+#[cfg(target_os = "windows")]
+use winapi::um::winuser::*;
+#[cfg(target_os = "windows")]
+use std::ffi::OsString;
+#[cfg(target_os = "windows")]
+use std::os::windows::ffi::OsStringExt;
+#[cfg(target_os = "windows")]
+use std::mem::{size_of, zeroed};
+
+#[cfg(target_os = "linux")]
+use libxdo_sys::{xdo_new, xdo_mouse_down, xdo_mouse_up, xdo_click_window, xdo_send_keysequence_window,
+    xdo_send_keysequence_window_down, xdo_send_keysequence_window_up, xdo_enter_text_window,
+    xdo_move_mouse, xdo_free, xdo, CURRENTWINDOW};
+#[cfg(target_os = "linux")]
+use std::ffi::CString;
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    X1,
+    X2,
+}
+
+impl MouseButton {
+    pub fn to_usize(self) -> usize {
+        match self {
+            MouseButton::Left => 0,
+            MouseButton::Right => 1,
+            MouseButton::Middle => 2,
+            MouseButton::X1 => 3,
+            MouseButton::X2 => 4,
+        }
+    }
+
+    pub fn from_usize(value: usize) -> Self {
+        match value {
+            1 => MouseButton::Right,
+            2 => MouseButton::Middle,
+            3 => MouseButton::X1,
+            4 => MouseButton::X2,
+            _ => MouseButton::Left,
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum ScrollDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl ScrollDirection {
+    pub fn to_usize(self) -> usize {
+        match self {
+            ScrollDirection::Up => 0,
+            ScrollDirection::Down => 1,
+            ScrollDirection::Left => 2,
+            ScrollDirection::Right => 3,
+        }
+    }
+
+    pub fn from_usize(value: usize) -> Self {
+        match value {
+            0 => ScrollDirection::Up,
+            1 => ScrollDirection::Down,
+            2 => ScrollDirection::Left,
+            3 => ScrollDirection::Right,
+            _ => ScrollDirection::Down,
+        }
+    }
+}
+
+// Keys we can bind or send: display name, Windows virtual-key code, X11 keysym and xdo name
+const KEYS: [(&str, u16, u32, &str); 67] = [
+    ("A", 0x41, 0x61, "a"),
+    ("B", 0x42, 0x62, "b"),
+    ("C", 0x43, 0x63, "c"),
+    ("D", 0x44, 0x64, "d"),
+    ("E", 0x45, 0x65, "e"),
+    ("F", 0x46, 0x66, "f"),
+    ("G", 0x47, 0x67, "g"),
+    ("H", 0x48, 0x68, "h"),
+    ("I", 0x49, 0x69, "i"),
+    ("J", 0x4a, 0x6a, "j"),
+    ("K", 0x4b, 0x6b, "k"),
+    ("L", 0x4c, 0x6c, "l"),
+    ("M", 0x4d, 0x6d, "m"),
+    ("N", 0x4e, 0x6e, "n"),
+    ("O", 0x4f, 0x6f, "o"),
+    ("P", 0x50, 0x70, "p"),
+    ("Q", 0x51, 0x71, "q"),
+    ("R", 0x52, 0x72, "r"),
+    ("S", 0x53, 0x73, "s"),
+    ("T", 0x54, 0x74, "t"),
+    ("U", 0x55, 0x75, "u"),
+    ("V", 0x56, 0x76, "v"),
+    ("W", 0x57, 0x77, "w"),
+    ("X", 0x58, 0x78, "x"),
+    ("Y", 0x59, 0x79, "y"),
+    ("Z", 0x5a, 0x7a, "z"),
+    ("0", 0x30, 0x30, "0"),
+    ("1", 0x31, 0x31, "1"),
+    ("2", 0x32, 0x32, "2"),
+    ("3", 0x33, 0x33, "3"),
+    ("4", 0x34, 0x34, "4"),
+    ("5", 0x35, 0x35, "5"),
+    ("6", 0x36, 0x36, "6"),
+    ("7", 0x37, 0x37, "7"),
+    ("8", 0x38, 0x38, "8"),
+    ("9", 0x39, 0x39, "9"),
+    ("F1", 0x70, 0xffbe, "F1"),
+    ("F2", 0x71, 0xffbf, "F2"),
+    ("F3", 0x72, 0xffc0, "F3"),
+    ("F4", 0x73, 0xffc1, "F4"),
+    ("F5", 0x74, 0xffc2, "F5"),
+    ("F6", 0x75, 0xffc3, "F6"),
+    ("F7", 0x76, 0xffc4, "F7"),
+    ("F8", 0x77, 0xffc5, "F8"),
+    ("F9", 0x78, 0xffc6, "F9"),
+    ("F10", 0x79, 0xffc7, "F10"),
+    ("F11", 0x7a, 0xffc8, "F11"),
+    ("F12", 0x7b, 0xffc9, "F12"),
+    ("Space", 0x20, 0x20, "space"),
+    ("Enter", 0x0d, 0xff0d, "Return"),
+    ("Tab", 0x09, 0xff09, "Tab"),
+    ("Escape", 0x1b, 0xff1b, "Escape"),
+    ("Backspace", 0x08, 0xff08, "BackSpace"),
+    ("Delete", 0x2e, 0xffff, "Delete"),
+    ("Insert", 0x2d, 0xff63, "Insert"),
+    ("Home", 0x24, 0xff50, "Home"),
+    ("End", 0x23, 0xff57, "End"),
+    ("PageUp", 0x21, 0xff55, "Prior"),
+    ("PageDown", 0x22, 0xff56, "Next"),
+    ("Up", 0x26, 0xff52, "Up"),
+    ("Down", 0x28, 0xff54, "Down"),
+    ("Left", 0x25, 0xff51, "Left"),
+    ("Right", 0x27, 0xff53, "Right"),
+    ("Shift", 0xa0, 0xffe1, "shift"),
+    ("Ctrl", 0xa2, 0xffe3, "ctrl"),
+    ("Alt", 0xa4, 0xffe9, "alt"),
+    ("Super", 0x5b, 0xffeb, "super"),
+];
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Key(usize);
+
+impl Key {
+    pub fn all() -> impl Iterator<Item = Key> {
+        (0..KEYS.len()).map(Key)
+    }
+
+    // Case-insensitive, also accepts the xdo spelling ("ctrl", "Return", "Prior")
+    pub fn from_name(name: &str) -> Option<Key> {
+        KEYS.iter()
+            .position(|(display, _, _, xdo)| display.eq_ignore_ascii_case(name) || xdo.eq_ignore_ascii_case(name))
+            .map(Key)
+    }
+
+    pub fn name(self) -> &'static str {
+        KEYS[self.0].0
+    }
+
+    // Keys are stored in atomics as index + 1 so that 0 can mean "no key"
+    pub fn to_usize(key: Option<Key>) -> usize {
+        key.map_or(0, |key| key.0 + 1)
+    }
+
+    pub fn from_usize(value: usize) -> Option<Key> {
+        (value > 0 && value <= KEYS.len()).then(|| Key(value - 1))
+    }
+
+    #[cfg(target_os = "windows")]
+    pub fn vk(self) -> u16 {
+        KEYS[self.0].1
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn keysym(self) -> u32 {
+        KEYS[self.0].2
+    }
+
+    // Also the keysym name xdotool takes
+    pub fn xdo_name(self) -> &'static str {
+        KEYS[self.0].3
+    }
+
+    // Right-hand and generic modifier codes count as the left-hand key
+    #[cfg(target_os = "windows")]
+    pub fn from_vk(vk: u16) -> Option<Key> {
+        let vk = match vk {
+            0x10 | 0xa1 => 0xa0,
+            0x11 | 0xa3 => 0xa2,
+            0x12 | 0xa5 => 0xa4,
+            0x5c => 0x5b,
+            vk => vk,
+        };
+        KEYS.iter().position(|&(_, key_vk, _, _)| key_vk == vk).map(Key)
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn from_keysym(keysym: u32) -> Option<Key> {
+        let keysym = match keysym {
+            0xffe2 => 0xffe1,
+            0xffe4 => 0xffe3,
+            0xffea => 0xffe9,
+            0xffec => 0xffeb,
+            keysym => keysym,
+        };
+        KEYS.iter().position(|&(_, _, key_keysym, _)| key_keysym == keysym).map(Key)
+    }
+}
+
+// Parse a combo like "ctrl+shift+s", keys are pressed in order and released in reverse
+pub fn parse_key_combo(combo: &str) -> Result<Vec<Key>, String> {
+    if combo.trim().is_empty() {
+        return Err("Key combo is empty".to_string());
+    }
+
+    combo
+        .split('+')
+        .map(|name| {
+            let name = name.trim();
+            Key::from_name(name).ok_or_else(|| format!("Unknown key '{}'", name))
+        })
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+pub fn get_active_window_title() -> String {
+    unsafe {
+        let display = x11::xlib::XOpenDisplay(std::ptr::null());
+        if display.is_null() {
+            return String::new();
+        }
+
+        let mut window: x11::xlib::Window = 0;
+        let mut revert: i32 = 0;
+        x11::xlib::XGetInputFocus(display, &mut window, &mut revert);
+
+        let mut name: *mut i8 = std::ptr::null_mut();
+        x11::xlib::XFetchName(display, window, &mut name);
+
+        x11::xlib::XCloseDisplay(display);
+
+        if !name.is_null() {
+            let title = CString::from_raw(name).into_string().unwrap_or_default();
+            title
+        } else {
+            String::new()
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+pub fn get_active_window_title() -> String {
+    unsafe {
+        let hwnd = GetForegroundWindow();
+        let mut title = [0u16; 512];
+        let len = GetWindowTextW(hwnd, title.as_mut_ptr(), title.len() as i32);
+        if len > 0 {
+            OsString::from_wide(&title[..len as usize]).to_string_lossy().into_owned()
+        } else {
+            String::new()
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn get_active_window() -> x11::xlib::Window {
+    unsafe {
+        let display = x11::xlib::XOpenDisplay(std::ptr::null());
+        if display.is_null() {
+            return 0;
+        }
+
+        let mut window: x11::xlib::Window = 0;
+        let mut revert: i32 = 0;
+        x11::xlib::XGetInputFocus(display, &mut window, &mut revert);
+
+        x11::xlib::XCloseDisplay(display);
+        window
+    }
+}
+
+// Missing from libxdo-sys, but part of the libxdo we already link against
+#[cfg(target_os = "linux")]
+extern "C" {
+    fn xdo_get_window_location(
+        xdo: *const xdo,
+        window: x11::xlib::Window,
+        x: *mut std::os::raw::c_int,
+        y: *mut std::os::raw::c_int,
+        screen: *mut *mut x11::xlib::Screen,
+    ) -> std::os::raw::c_int;
+}
+
+// Whether a window title or class contains the query, ignoring case
+fn window_matches(query: &str, title: &str, class: &str) -> bool {
+    let query = query.to_lowercase();
+    title.to_lowercase().contains(&query) || class.to_lowercase().contains(&query)
+}
+
+// Windows can close while the tree is walked, the error for that is ignored instead of
+// ending the process. The handler is global, so only one walk installs it at a time.
+#[cfg(target_os = "linux")]
+static WINDOW_WALK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+#[cfg(target_os = "linux")]
+unsafe extern "C" fn ignore_x_error(_: *mut x11::xlib::Display, _: *mut x11::xlib::XErrorEvent) -> std::os::raw::c_int {
+    0
+}
+
+#[cfg(target_os = "linux")]
+unsafe fn find_window(display: *mut x11::xlib::Display, window: x11::xlib::Window, query: &str) -> Option<x11::xlib::Window> {
+    let mut attributes: x11::xlib::XWindowAttributes = std::mem::zeroed();
+    if x11::xlib::XGetWindowAttributes(display, window, &mut attributes) == 0 {
+        return None;
+    }
+    if attributes.map_state == x11::xlib::IsViewable {
+        let mut title = String::new();
+        let mut name: *mut i8 = std::ptr::null_mut();
+        if x11::xlib::XFetchName(display, window, &mut name) != 0 && !name.is_null() {
+            title = std::ffi::CStr::from_ptr(name).to_string_lossy().into_owned();
+            x11::xlib::XFree(name as *mut _);
+        }
+
+        let mut class = String::new();
+        let mut hint: x11::xlib::XClassHint = std::mem::zeroed();
+        if x11::xlib::XGetClassHint(display, window, &mut hint) != 0 {
+            if !hint.res_class.is_null() {
+                class = std::ffi::CStr::from_ptr(hint.res_class).to_string_lossy().into_owned();
+                x11::xlib::XFree(hint.res_class as *mut _);
+            }
+            if !hint.res_name.is_null() {
+                x11::xlib::XFree(hint.res_name as *mut _);
+            }
+        }
+
+        if (!title.is_empty() || !class.is_empty()) && window_matches(query, &title, &class) {
+            return Some(window);
+        }
+    }
+
+    let (mut root, mut parent) = (0, 0);
+    let mut children: *mut x11::xlib::Window = std::ptr::null_mut();
+    let mut count = 0;
+    if x11::xlib::XQueryTree(display, window, &mut root, &mut parent, &mut children, &mut count) == 0 {
+        return None;
+    }
+
+    let mut found = None;
+    if !children.is_null() {
+        for &child in std::slice::from_raw_parts(children, count as usize) {
+            found = find_window(display, child, query);
+            if found.is_some() {
+                break;
+            }
+        }
+        x11::xlib::XFree(children as *mut _);
+    }
+    found
+}
+
+#[cfg(target_os = "windows")]
+unsafe extern "system" fn find_window_proc(
+    hwnd: winapi::shared::windef::HWND,
+    l_param: winapi::shared::minwindef::LPARAM,
+) -> winapi::shared::minwindef::BOOL {
+    let search = &mut *(l_param as *mut (String, Option<winapi::shared::windef::HWND>));
+    if IsWindowVisible(hwnd) == 0 {
+        return 1;
+    }
+
+    let mut title = [0u16; 512];
+    let len = GetWindowTextW(hwnd, title.as_mut_ptr(), title.len() as i32).max(0) as usize;
+    let mut class = [0u16; 256];
+    let class_len = GetClassNameW(hwnd, class.as_mut_ptr(), class.len() as i32).max(0) as usize;
+
+    let title = OsString::from_wide(&title[..len]).to_string_lossy().into_owned();
+    let class = OsString::from_wide(&class[..class_len]).to_string_lossy().into_owned();
+    if window_matches(&search.0, &title, &class) {
+        search.1 = Some(hwnd);
+        return 0;
+    }
+    1
+}
+
+// Screen position of the top-left corner of the first visible window whose title or class
+// contains the query
+pub fn find_window_origin(query: &str) -> Option<(i32, i32)> {
+    #[cfg(target_os = "windows")]
+    unsafe {
+        let mut search = (query.to_string(), None);
+        EnumWindows(Some(find_window_proc), &mut search as *mut _ as isize);
+        let hwnd = search.1?;
+
+        let mut rect: winapi::shared::windef::RECT = zeroed();
+        if GetWindowRect(hwnd, &mut rect) == 0 {
+            return None;
+        }
+        Some((rect.left, rect.top))
+    }
+    #[cfg(target_os = "linux")]
+    unsafe {
+        let display = x11::xlib::XOpenDisplay(std::ptr::null());
+        if display.is_null() {
+            return None;
+        }
+
+        let _walk = WINDOW_WALK.lock().unwrap_or_else(|error| error.into_inner());
+        let previous = x11::xlib::XSetErrorHandler(Some(ignore_x_error));
+        let window = find_window(display, x11::xlib::XDefaultRootWindow(display), query);
+
+        // Still ignoring errors, the window can close before it is located too
+        let xdo = xdo_new(std::ptr::null());
+        let (mut x, mut y) = (0, 0);
+        let located = !xdo.is_null()
+            && window.is_some_and(|window| xdo_get_window_location(xdo, window, &mut x, &mut y, std::ptr::null_mut()) == 0);
+        if !xdo.is_null() {
+            x11::xlib::XSync((*xdo).xdpy, x11::xlib::False);
+            xdo_free(xdo);
+        }
+
+        // Errors are reported asynchronously, let them all arrive before the handler goes
+        x11::xlib::XSync(display, x11::xlib::False);
+        x11::xlib::XSetErrorHandler(previous);
+        x11::xlib::XCloseDisplay(display);
+        located.then_some((x, y))
+    }
+}
+
+// The system limit between presses for them to still count as one double-click
+pub fn double_click_time() -> Duration {
+    #[cfg(target_os = "windows")]
+    unsafe {
+        Duration::from_millis(GetDoubleClickTime() as u64)
+    }
+
+    // X11 has no server-wide setting, 400ms is the GTK and Qt default
+    #[cfg(target_os = "linux")]
+    Duration::from_millis(400)
+}
+
+// Pointer position in screen coordinates
+pub fn cursor_position() -> (i32, i32) {
+    #[cfg(target_os = "windows")]
+    unsafe {
+        let mut point: winapi::shared::windef::POINT = zeroed();
+        GetCursorPos(&mut point);
+        (point.x, point.y)
+    }
+    #[cfg(target_os = "linux")]
+    unsafe {
+        let display = x11::xlib::XOpenDisplay(std::ptr::null());
+        if display.is_null() {
+            return (0, 0);
+        }
+
+        let root = x11::xlib::XDefaultRootWindow(display);
+        let (mut root_return, mut child_return) = (0, 0);
+        let (mut x, mut y, mut win_x, mut win_y) = (0, 0, 0, 0);
+        let mut mask = 0;
+        x11::xlib::XQueryPointer(
+            display, root, &mut root_return, &mut child_return,
+            &mut x, &mut y, &mut win_x, &mut win_y, &mut mask,
+        );
+
+        x11::xlib::XCloseDisplay(display);
+        (x, y)
+    }
+}
+
+// Colour of one screen pixel as (r, g, b), None if the screen can't be read there
+pub fn pixel_color(x: i32, y: i32) -> Option<(u8, u8, u8)> {
+    #[cfg(target_os = "windows")]
+    unsafe {
+        let dc = GetDC(std::ptr::null_mut());
+        if dc.is_null() {
+            return None;
+        }
+        let color = winapi::um::wingdi::GetPixel(dc, x, y);
+        ReleaseDC(std::ptr::null_mut(), dc);
+
+        // CLR_INVALID, the point is off screen
+        if color == 0xFFFFFFFF {
+            return None;
+        }
+        Some(((color & 0xFF) as u8, ((color >> 8) & 0xFF) as u8, ((color >> 16) & 0xFF) as u8))
+    }
+    #[cfg(target_os = "linux")]
+    unsafe {
+        let display = x11::xlib::XOpenDisplay(std::ptr::null());
+        if display.is_null() {
+            return None;
+        }
+
+        // XGetImage outside the root window is a BadMatch, which would end the process
+        let screen = x11::xlib::XDefaultScreen(display);
+        let (width, height) = (x11::xlib::XDisplayWidth(display, screen), x11::xlib::XDisplayHeight(display, screen));
+        if x < 0 || y < 0 || x >= width || y >= height {
+            x11::xlib::XCloseDisplay(display);
+            return None;
+        }
+
+        let root = x11::xlib::XDefaultRootWindow(display);
+        let image = x11::xlib::XGetImage(display, root, x, y, 1, 1, !0, x11::xlib::ZPixmap);
+        let color = if image.is_null() {
+            None
+        } else {
+            let pixel = x11::xlib::XGetPixel(image, 0, 0);
+            // Scale each channel by its mask so 16 bit visuals come out in 0-255 too
+            let channel = |mask: u64| {
+                if mask == 0 {
+                    return 0;
+                }
+                let value = (pixel & mask) >> mask.trailing_zeros();
+                (value * 255 / (mask >> mask.trailing_zeros())) as u8
+            };
+            let color = ((*image).red_mask, (*image).green_mask, (*image).blue_mask);
+            let rgb = (channel(color.0), channel(color.1), channel(color.2));
+            x11::xlib::XDestroyImage(image);
+            Some(rgb)
+        };
+
+        x11::xlib::XCloseDisplay(display);
+        color
+    }
+}
+
+// Warp the pointer to a screen coordinate
+pub fn move_pointer(x: i32, y: i32) {
+    #[cfg(target_os = "windows")]
+    unsafe {
+        SetCursorPos(x, y);
+    }
+    #[cfg(target_os = "linux")]
+    unsafe {
+        let xdo = xdo_new(std::ptr::null());
+        if !xdo.is_null() {
+            xdo_move_mouse(xdo, x, y, 0);
+            xdo_free(xdo);
+        }
+    }
+}
+
+// Press and release a mouse button at the current pointer position
+pub fn send_click(button: MouseButton) {
+    send_button(button, true);
+    thread::sleep(Duration::from_millis(1));
+    send_button(button, false);
+}
+
+// A mouse button pressed down until the guard is dropped, so a drag never leaves it stuck
+pub struct HeldButton(MouseButton);
+
+pub fn hold_button(button: MouseButton) -> HeldButton {
+    send_button(button, true);
+    HeldButton(button)
+}
+
+impl Drop for HeldButton {
+    fn drop(&mut self) {
+        send_button(self.0, false);
+    }
+}
+
+fn send_button(button: MouseButton, down: bool) {
+    #[cfg(target_os = "windows")]
+    unsafe {
+        let (down_flag, up_flag, data) = match button {
+            MouseButton::Left => (MOUSEEVENTF_LEFTDOWN, MOUSEEVENTF_LEFTUP, 0),
+            MouseButton::Right => (MOUSEEVENTF_RIGHTDOWN, MOUSEEVENTF_RIGHTUP, 0),
+            MouseButton::Middle => (MOUSEEVENTF_MIDDLEDOWN, MOUSEEVENTF_MIDDLEUP, 0),
+            MouseButton::X1 => (MOUSEEVENTF_XDOWN, MOUSEEVENTF_XUP, XBUTTON1 as u32),
+            MouseButton::X2 => (MOUSEEVENTF_XDOWN, MOUSEEVENTF_XUP, XBUTTON2 as u32),
+        };
+        mouse_event(if down { down_flag } else { up_flag }, 0, 0, data, 0);
+    }
+    #[cfg(target_os = "linux")]
+    unsafe {
+        let xbutton = match button {
+            MouseButton::Left => 1,
+            MouseButton::Right => 3,
+            MouseButton::Middle => 2,
+            MouseButton::X1 => 8,
+            MouseButton::X2 => 9,
+        };
+        // Through XTEST (no window) so the server really holds the button, the pointer
+        // motion during a drag carries it and the release lands wherever the pointer is
+        let xdo = xdo_new(std::ptr::null());
+        if !xdo.is_null() {
+            if down {
+                xdo_mouse_down(xdo, CURRENTWINDOW, xbutton);
+            } else {
+                xdo_mouse_up(xdo, CURRENTWINDOW, xbutton);
+            }
+            xdo_free(xdo);
+        }
+    }
+}
+
+// Turn the wheel by a number of notches at the current pointer position
+pub fn send_scroll(direction: ScrollDirection, notches: u32) {
+    #[cfg(target_os = "windows")]
+    unsafe {
+        // Positive wheel data scrolls up and right
+        let delta = WHEEL_DELTA as i32 * notches as i32;
+        let (flags, data) = match direction {
+            ScrollDirection::Up => (MOUSEEVENTF_WHEEL, delta),
+            ScrollDirection::Down => (MOUSEEVENTF_WHEEL, -delta),
+            ScrollDirection::Left => (MOUSEEVENTF_HWHEEL, -delta),
+            ScrollDirection::Right => (MOUSEEVENTF_HWHEEL, delta),
+        };
+        mouse_event(flags, 0, 0, data as u32, 0);
+    }
+    #[cfg(target_os = "linux")]
+    unsafe {
+        // X11 reports each wheel notch as a click of buttons 4-7
+        let xbutton = match direction {
+            ScrollDirection::Up => 4,
+            ScrollDirection::Down => 5,
+            ScrollDirection::Left => 6,
+            ScrollDirection::Right => 7,
+        };
+        let target_window = get_active_window();
+        if target_window != 0 {
+            let xdo = xdo_new(std::ptr::null());
+            if !xdo.is_null() {
+                for _ in 0..notches {
+                    xdo_click_window(xdo, target_window, xbutton);
+                }
+                xdo_free(xdo);
+            }
+        }
+    }
+}
+
+// Tap a key combo in the focused window
+pub fn send_keys(keys: &[Key]) {
+    send_key_events(keys, true, true);
+}
+
+// Keys pressed down until the guard is dropped, so they come back up even if the
+// holder stops early or panics
+pub struct HeldKeys(Vec<Key>);
+
+pub fn hold_keys(keys: &[Key]) -> HeldKeys {
+    if !keys.is_empty() {
+        send_key_events(keys, true, false);
+    }
+    HeldKeys(keys.to_vec())
+}
+
+impl Drop for HeldKeys {
+    fn drop(&mut self) {
+        if !self.0.is_empty() {
+            send_key_events(&self.0, false, true);
+        }
+    }
+}
+
+// Press the keys in order and/or release them in reverse order
+fn send_key_events(keys: &[Key], down: bool, up: bool) {
+    #[cfg(target_os = "windows")]
+    unsafe {
+        let mut inputs = Vec::new();
+        let presses = keys.iter().filter(|_| down).map(|key| (key, 0));
+        let releases = keys.iter().rev().filter(|_| up).map(|key| (key, KEYEVENTF_KEYUP));
+        for (key, flags) in presses.chain(releases) {
+            let mut input: INPUT = zeroed();
+            input.type_ = INPUT_KEYBOARD;
+            *input.u.ki_mut() = KEYBDINPUT {
+                wVk: key.vk(),
+                wScan: 0,
+                dwFlags: flags,
+                time: 0,
+                dwExtraInfo: 0,
+            };
+            inputs.push(input);
+        }
+        SendInput(inputs.len() as u32, inputs.as_mut_ptr(), size_of::<INPUT>() as i32);
+    }
+    #[cfg(target_os = "linux")]
+    unsafe {
+        let sequence = keys.iter().map(|key| key.xdo_name()).collect::<Vec<_>>().join("+");
+        let sequence = CString::new(sequence).unwrap();
+        // Held keys go through XTEST (no window), events sent to a window don't change the
+        // server's modifier state, so the clicks in between wouldn't carry them
+        let target_window = if down && up { get_active_window() } else { CURRENTWINDOW };
+        if down && up && target_window == 0 {
+            return;
+        }
+        let xdo = xdo_new(std::ptr::null());
+        if !xdo.is_null() {
+            match (down, up) {
+                (true, false) => xdo_send_keysequence_window_down(xdo, target_window, sequence.as_ptr(), 0),
+                (false, true) => xdo_send_keysequence_window_up(xdo, target_window, sequence.as_ptr(), 0),
+                _ => xdo_send_keysequence_window(xdo, target_window, sequence.as_ptr(), 12000),
+            };
+            xdo_free(xdo);
+        }
+    }
+}
+
+// Type text into the focused window as-is, without going through key bindings
+pub fn send_text(text: &str) {
+    #[cfg(target_os = "windows")]
+    unsafe {
+        let mut inputs = Vec::new();
+        for unit in text.encode_utf16() {
+            for flags in [KEYEVENTF_UNICODE, KEYEVENTF_UNICODE | KEYEVENTF_KEYUP] {
+                let mut input: INPUT = zeroed();
+                input.type_ = INPUT_KEYBOARD;
+                *input.u.ki_mut() = KEYBDINPUT {
+                    wVk: 0,
+                    wScan: unit,
+                    dwFlags: flags,
+                    time: 0,
+                    dwExtraInfo: 0,
+                };
+                inputs.push(input);
+            }
+        }
+        SendInput(inputs.len() as u32, inputs.as_mut_ptr(), size_of::<INPUT>() as i32);
+    }
+    #[cfg(target_os = "linux")]
+    unsafe {
+        let target_window = get_active_window();
+        if target_window != 0 {
+            let xdo = xdo_new(std::ptr::null());
+            if !xdo.is_null() {
+                if let Ok(text) = CString::new(text) {
+                    xdo_enter_text_window(xdo, target_window, text.as_ptr(), 12000);
+                }
+                xdo_free(xdo);
+            }
+        }
+    }
+}
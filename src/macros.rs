@@ -0,0 +1,790 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::input::{get_active_window_title, parse_key_combo, pixel_color, Key, MouseButton, ScrollDirection};
+
+// First line of every macro file, bumped when the format changes incompatibly
+pub const HEADER: &str = "# rust_clicker macro v2";
+
+// Older headers that still parse, every v1 macro is a valid v2 macro
+const OLD_HEADERS: [&str; 1] = ["# rust_clicker macro v1"];
+
+// Calls nested deeper than this stop playback instead of overflowing the stack
+pub const MAX_CALL_DEPTH: usize = 32;
+
+// One step of a macro, waits carry the timing between the others
+#[derive(PartialEq, Clone, Debug)]
+pub enum MacroStep {
+    Move(i32, i32),
+    Click(MouseButton, u32),
+    Press(MouseButton),
+    Release(MouseButton),
+    Scroll(ScrollDirection, u32),
+    Keys(Vec<Key>),
+    KeyDown(Key),
+    KeyUp(Key),
+    Wait(u32),
+    Drag((i32, i32), (i32, i32), MouseButton),
+    Loop(Value, Vec<MacroStep>),
+    While(Condition, Vec<MacroStep>),
+    If(Condition, Vec<MacroStep>, Vec<MacroStep>),
+    Set(String, Value),
+    Add(String, Value),
+    // A named block that only runs when called, only allowed at the top level
+    Sub(String, Vec<MacroStep>),
+    Call(String),
+}
+
+// A number or the current value of a variable, unset variables are 0
+#[derive(PartialEq, Clone, Debug)]
+pub enum Value {
+    Number(i64),
+    Var(String),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Number(number) => write!(f, "{}", number),
+            Value::Var(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Compare {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Compare {
+    const ALL: [Compare; 6] = [
+        Compare::Less,
+        Compare::LessOrEqual,
+        Compare::Greater,
+        Compare::GreaterOrEqual,
+        Compare::Equal,
+        Compare::NotEqual,
+    ];
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Compare::Less => "<",
+            Compare::LessOrEqual => "<=",
+            Compare::Greater => ">",
+            Compare::GreaterOrEqual => ">=",
+            Compare::Equal => "==",
+            Compare::NotEqual => "!=",
+        }
+    }
+
+    pub fn holds(self, left: i64, right: i64) -> bool {
+        match self {
+            Compare::Less => left < right,
+            Compare::LessOrEqual => left <= right,
+            Compare::Greater => left > right,
+            Compare::GreaterOrEqual => left >= right,
+            Compare::Equal => left == right,
+            Compare::NotEqual => left != right,
+        }
+    }
+}
+
+// What `if` and `while` test
+#[derive(PartialEq, Clone, Debug)]
+pub enum Condition {
+    // The focused window's title contains the text, ignoring case
+    Window(String),
+    // A screen pixel is within the tolerance of a colour on every channel
+    Pixel(i32, i32, (u8, u8, u8), u8),
+    // Milliseconds since playback started
+    Elapsed(Compare, u32),
+    Var(String, Compare, Value),
+    Not(Box<Condition>),
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Condition::Window(title) => write!(f, "window {}", title),
+            Condition::Pixel(x, y, (r, g, b), 0) => write!(f, "pixel {} {} #{:02x}{:02x}{:02x}", x, y, r, g, b),
+            Condition::Pixel(x, y, (r, g, b), tolerance) => {
+                write!(f, "pixel {} {} #{:02x}{:02x}{:02x} {}", x, y, r, g, b, tolerance)
+            }
+            Condition::Elapsed(compare, ms) => write!(f, "elapsed {} {}ms", compare.symbol(), ms),
+            Condition::Var(name, compare, value) => write!(f, "{} {} {}", name, compare.symbol(), value),
+            Condition::Not(condition) => write!(f, "not {}", condition),
+        }
+    }
+}
+
+pub fn button_name(button: MouseButton) -> &'static str {
+    match button {
+        MouseButton::Left => "left",
+        MouseButton::Right => "right",
+        MouseButton::Middle => "middle",
+        MouseButton::X1 => "x1",
+        MouseButton::X2 => "x2",
+    }
+}
+
+pub fn direction_name(direction: ScrollDirection) -> &'static str {
+    match direction {
+        ScrollDirection::Up => "up",
+        ScrollDirection::Down => "down",
+        ScrollDirection::Left => "left",
+        ScrollDirection::Right => "right",
+    }
+}
+
+impl fmt::Display for MacroStep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MacroStep::Move(x, y) => write!(f, "move {} {}", x, y),
+            MacroStep::Click(button, 1) => write!(f, "click {}", button_name(*button)),
+            MacroStep::Click(button, clicks) => write!(f, "click {} x{}", button_name(*button), clicks),
+            MacroStep::Press(button) => write!(f, "down {}", button_name(*button)),
+            MacroStep::Release(button) => write!(f, "up {}", button_name(*button)),
+            MacroStep::Scroll(direction, notches) => write!(f, "scroll {} {}", direction_name(*direction), notches),
+            MacroStep::Keys(keys) => {
+                let combo = keys.iter().map(|key| key.name().to_lowercase()).collect::<Vec<_>>().join("+");
+                write!(f, "key {}", combo)
+            }
+            MacroStep::KeyDown(key) => write!(f, "keydown {}", key.name().to_lowercase()),
+            MacroStep::KeyUp(key) => write!(f, "keyup {}", key.name().to_lowercase()),
+            MacroStep::Wait(ms) => write!(f, "wait {}ms", ms),
+            MacroStep::Drag(from, to, button) => {
+                write!(f, "drag {} {} {} {} {}", from.0, from.1, to.0, to.1, button_name(*button))
+            }
+            // Bodies are written by `to_text`, one step per line
+            MacroStep::Loop(count, _) => write!(f, "loop {} {{", count),
+            MacroStep::While(condition, _) => write!(f, "while {} {{", condition),
+            MacroStep::If(condition, _, _) => write!(f, "if {} {{", condition),
+            MacroStep::Set(name, value) => write!(f, "set {} {}", name, value),
+            MacroStep::Add(name, value) => write!(f, "add {} {}", name, value),
+            MacroStep::Sub(name, _) => write!(f, "sub {} {{", name),
+            MacroStep::Call(name) => write!(f, "call {}", name),
+        }
+    }
+}
+
+// Position of the first pointer move or drag, looking inside blocks
+pub fn first_move(steps: &[MacroStep]) -> Option<(i32, i32)> {
+    steps.iter().find_map(|step| match step {
+        MacroStep::Move(x, y) => Some((*x, *y)),
+        MacroStep::Drag(from, _, _) => Some(*from),
+        MacroStep::Loop(_, body) | MacroStep::While(_, body) => first_move(body),
+        MacroStep::If(_, then, otherwise) => first_move(then).or_else(|| first_move(otherwise)),
+        _ => None,
+    })
+}
+
+// Variables and sub-macros of one playback, plus what the debugger shows
+pub struct Interpreter {
+    vars: BTreeMap<String, i64>,
+    subs: HashMap<String, Vec<MacroStep>>,
+    started: Instant,
+    pub depth: usize,
+}
+
+impl Interpreter {
+    pub fn new(steps: &[MacroStep]) -> Self {
+        let subs = steps
+            .iter()
+            .filter_map(|step| match step {
+                MacroStep::Sub(name, body) => Some((name.clone(), body.clone())),
+                _ => None,
+            })
+            .collect();
+        Self { vars: BTreeMap::new(), subs, started: Instant::now(), depth: 0 }
+    }
+
+    pub fn value(&self, value: &Value) -> i64 {
+        match value {
+            Value::Number(number) => *number,
+            Value::Var(name) => self.vars.get(name).copied().unwrap_or(0),
+        }
+    }
+
+    pub fn set(&mut self, name: &str, value: i64) {
+        self.vars.insert(name.to_string(), value);
+    }
+
+    pub fn vars(&self) -> Vec<(String, i64)> {
+        self.vars.iter().map(|(name, value)| (name.clone(), *value)).collect()
+    }
+
+    pub fn sub(&self, name: &str) -> Option<Vec<MacroStep>> {
+        self.subs.get(name).cloned()
+    }
+
+    pub fn test(&self, condition: &Condition) -> bool {
+        match condition {
+            Condition::Window(title) => get_active_window_title().to_lowercase().contains(&title.to_lowercase()),
+            Condition::Pixel(x, y, (r, g, b), tolerance) => pixel_color(*x, *y).is_some_and(|(pr, pg, pb)| {
+                let close = |a: u8, b: u8| a.abs_diff(b) <= *tolerance;
+                close(pr, *r) && close(pg, *g) && close(pb, *b)
+            }),
+            Condition::Elapsed(compare, ms) => compare.holds(self.started.elapsed().as_millis() as i64, *ms as i64),
+            Condition::Var(name, compare, value) => {
+                compare.holds(self.value(&Value::Var(name.clone())), self.value(value))
+            }
+            Condition::Not(condition) => !self.test(condition),
+        }
+    }
+}
+
+// Real user input as seen by the platform hooks
+pub enum RecordedInput {
+    Move(i32, i32),
+    Button(MouseButton, bool),
+    Scroll(ScrollDirection),
+    Key(Key, bool),
+}
+
+// Pointer moves closer together than this are merged into one step
+const MOVE_INTERVAL: Duration = Duration::from_millis(20);
+
+// Turns a stream of timestamped input into macro steps
+pub struct Recorder {
+    steps: Vec<MacroStep>,
+    last_event: Instant,
+    last_move: Instant,
+    pending_move: Option<((i32, i32), Instant)>,
+    position: Option<(i32, i32)>,
+    held_buttons: Vec<MouseButton>,
+    held_keys: Vec<Key>,
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        let now = Instant::now();
+        Self {
+            steps: Vec::new(),
+            last_event: now,
+            last_move: now,
+            pending_move: None,
+            position: None,
+            held_buttons: Vec::new(),
+            held_keys: Vec::new(),
+        }
+    }
+}
+
+impl Recorder {
+    pub fn record(&mut self, input: RecordedInput) {
+        let now = Instant::now();
+        if let RecordedInput::Move(x, y) = input {
+            if now.duration_since(self.last_move) < MOVE_INTERVAL {
+                self.pending_move = Some(((x, y), now));
+            } else {
+                self.pending_move = None;
+                self.push_move(x, y, now);
+            }
+            return;
+        }
+
+        // Anything else happens at the latest pointer position
+        if let Some(((x, y), at)) = self.pending_move.take() {
+            self.push_move(x, y, at);
+        }
+
+        match input {
+            RecordedInput::Button(button, true) => {
+                self.held_buttons.push(button);
+                self.push(MacroStep::Press(button), now);
+            }
+            // Releases of presses from before recording started, like the Record button itself
+            RecordedInput::Button(button, false) => {
+                if let Some(i) = self.held_buttons.iter().position(|&held| held == button) {
+                    self.held_buttons.remove(i);
+                    self.push(MacroStep::Release(button), now);
+                }
+            }
+            RecordedInput::Scroll(direction) => self.push(MacroStep::Scroll(direction, 1), now),
+            RecordedInput::Key(key, true) => {
+                // Our own hotkeys stay out of the macro, and auto-repeat is one press
+                if matches!(key.name(), "F6" | "F7" | "F8") || self.held_keys.contains(&key) {
+                    return;
+                }
+                self.held_keys.push(key);
+                self.push(MacroStep::KeyDown(key), now);
+            }
+            RecordedInput::Key(key, false) => {
+                if let Some(i) = self.held_keys.iter().position(|&held| held == key) {
+                    self.held_keys.remove(i);
+                    self.push(MacroStep::KeyUp(key), now);
+                }
+            }
+            RecordedInput::Move(..) => {}
+        }
+    }
+
+    fn push_move(&mut self, x: i32, y: i32, at: Instant) {
+        if self.position != Some((x, y)) {
+            self.position = Some((x, y));
+            self.last_move = at;
+            self.push(MacroStep::Move(x, y), at);
+        }
+    }
+
+    fn push(&mut self, step: MacroStep, at: Instant) {
+        let waited = at.duration_since(self.last_event).as_millis() as u32;
+        if waited > 0 && !self.steps.is_empty() {
+            self.steps.push(MacroStep::Wait(waited));
+        }
+        self.last_event = at;
+        self.steps.push(step);
+    }
+
+    // The finished macro, with quick press/release pairs folded into clicks and key taps
+    pub fn finish(mut self) -> Vec<MacroStep> {
+        // Presses still held when recording stopped, like the Stop button, never happened
+        for button in self.held_buttons.drain(..) {
+            if let Some(i) = self.steps.iter().rposition(|step| *step == MacroStep::Press(button)) {
+                self.steps.remove(i);
+            }
+        }
+        for key in self.held_keys.drain(..) {
+            if let Some(i) = self.steps.iter().rposition(|step| *step == MacroStep::KeyDown(key)) {
+                self.steps.remove(i);
+            }
+        }
+
+        let mut steps: Vec<MacroStep> = Vec::new();
+        let mut i = 0;
+        while i < self.steps.len() {
+            let step = &self.steps[i];
+            let (wait, after) = match self.steps.get(i + 1) {
+                Some(MacroStep::Wait(ms)) => (Some(*ms), self.steps.get(i + 2)),
+                next => (None, next),
+            };
+
+            // Keep the press duration as a wait after the click so the timing still adds up
+            let folded = match (step, after) {
+                (MacroStep::Press(down), Some(MacroStep::Release(up))) if down == up => Some(MacroStep::Click(*down, 1)),
+                (MacroStep::KeyDown(down), Some(MacroStep::KeyUp(up))) if down == up => Some(MacroStep::Keys(vec![*down])),
+                _ => None,
+            };
+            match folded {
+                Some(folded) => {
+                    steps.push(folded);
+                    steps.extend(wait.map(MacroStep::Wait));
+                    i += if wait.is_some() { 3 } else { 2 };
+                }
+                None => {
+                    steps.push(step.clone());
+                    i += 1;
+                }
+            }
+        }
+
+        // Merge the waits left next to each other by removed presses
+        let mut merged: Vec<MacroStep> = Vec::new();
+        for step in steps {
+            match (merged.last_mut(), &step) {
+                (Some(MacroStep::Wait(total)), MacroStep::Wait(ms)) => *total += ms,
+                _ => merged.push(step),
+            }
+        }
+        while matches!(merged.last(), Some(MacroStep::Wait(_))) {
+            merged.pop();
+        }
+        merged
+    }
+}
+
+// The macro as text, one step per line with loop bodies indented
+pub fn to_text(steps: &[MacroStep]) -> String {
+    fn write_steps(text: &mut String, steps: &[MacroStep], depth: usize) {
+        let indent = "  ".repeat(depth);
+        for step in steps {
+            text.push_str(&indent);
+            text.push_str(&step.to_string());
+            text.push('\n');
+            match step {
+                MacroStep::Loop(_, body) | MacroStep::While(_, body) | MacroStep::Sub(_, body) => {
+                    write_steps(text, body, depth + 1);
+                }
+                MacroStep::If(_, then, otherwise) => {
+                    write_steps(text, then, depth + 1);
+                    if !otherwise.is_empty() {
+                        text.push_str(&indent);
+                        text.push_str("} else {\n");
+                        write_steps(text, otherwise, depth + 1);
+                    }
+                }
+                _ => continue,
+            }
+            text.push_str(&indent);
+            text.push_str("}\n");
+        }
+    }
+
+    let mut text = format!("{}\n", HEADER);
+    write_steps(&mut text, steps, 0);
+    text
+}
+
+// Where in the text a macro failed to parse, both counted from 1
+#[derive(PartialEq, Clone, Debug)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+// The words of a line with the column each starts at
+pub fn words(line: &str) -> Vec<(usize, &str)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (i, c) in line.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(from)) => {
+                words.push((from, &line[from..i]));
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(from) = start {
+        words.push((from, &line[from..]));
+    }
+    words
+}
+
+pub fn parse_button(word: &str) -> Option<MouseButton> {
+    [MouseButton::Left, MouseButton::Right, MouseButton::Middle, MouseButton::X1, MouseButton::X2]
+        .into_iter()
+        .find(|&button| button_name(button).eq_ignore_ascii_case(word))
+}
+
+fn parse_direction(word: &str) -> Option<ScrollDirection> {
+    [ScrollDirection::Up, ScrollDirection::Down, ScrollDirection::Left, ScrollDirection::Right]
+        .into_iter()
+        .find(|&direction| direction_name(direction).eq_ignore_ascii_case(word))
+}
+
+// "150ms", "1.5s" or a bare number of milliseconds
+fn parse_duration(word: &str) -> Option<u32> {
+    if let Some(ms) = word.strip_suffix("ms") {
+        ms.parse().ok()
+    } else if let Some(seconds) = word.strip_suffix('s') {
+        seconds.parse::<f64>().ok().filter(|s| *s >= 0.0).map(|s| (s * 1000.0).round() as u32)
+    } else {
+        word.parse().ok()
+    }
+}
+
+pub fn parse_macro(text: &str) -> Result<Vec<MacroStep>, ParseError> {
+    let error = |line: usize, column: usize, message: String| ParseError { line: line + 1, column: column + 1, message };
+
+    let mut lines = text.lines().enumerate();
+    match lines.next() {
+        Some((_, first)) if first.trim() == HEADER || OLD_HEADERS.contains(&first.trim()) => {}
+        Some((_, first)) if first.trim().starts_with("# rust_clicker macro ") => {
+            return Err(error(0, 0, format!("Unsupported macro version, expected '{}'", HEADER)));
+        }
+        _ => return Err(error(0, 0, format!("Missing '{}' header", HEADER))),
+    }
+
+    let mut open: Vec<OpenBlock> = Vec::new();
+    let mut steps = Vec::new();
+    // Every call with where it was made, checked once all subs are known
+    let mut calls = Vec::new();
+
+    for (number, line) in lines {
+        let words = words(line);
+        let Some(&(column, command)) = words.first() else {
+            continue;
+        };
+        if command.starts_with('#') {
+            continue;
+        }
+        let command = command.to_lowercase();
+
+        // Argument `i` of the command, or an error pointing just past the line
+        let argument = |i: usize, what: &str| {
+            words.get(i).copied().ok_or_else(|| error(number, line.trim_end().len(), format!("Expected {}", what)))
+        };
+        let number_at = |i: usize, what: &str| -> Result<i32, ParseError> {
+            let (column, word) = argument(i, what)?;
+            word.parse().map_err(|_| error(number, column, format!("Expected {}, found '{}'", what, word)))
+        };
+        let button_at = |i: usize| -> Result<MouseButton, ParseError> {
+            let (column, word) = argument(i, "a button")?;
+            parse_button(word).ok_or_else(|| error(number, column, format!("Unknown button '{}'", word)))
+        };
+        let key_at = |i: usize| -> Result<Key, ParseError> {
+            let (column, word) = argument(i, "a key")?;
+            Key::from_name(word).ok_or_else(|| error(number, column, format!("Unknown key '{}'", word)))
+        };
+        let name_at = |i: usize| -> Result<String, ParseError> {
+            let (column, word) = argument(i, "a name")?;
+            match is_name(word) {
+                true => Ok(word.to_string()),
+                false => Err(error(number, column, format!("'{}' can't be used as a name", word))),
+            }
+        };
+        let value_at = |i: usize| -> Result<Value, ParseError> {
+            let (column, word) = argument(i, "a number or variable")?;
+            parse_value(word).ok_or_else(|| error(number, column, format!("Expected a number or variable, found '{}'", word)))
+        };
+
+        if command == "}" {
+            let Some(block) = open.pop() else {
+                return Err(error(number, column, "'}' without a matching block".to_string()));
+            };
+            let rest = words[1..].iter().map(|&(_, word)| word).collect::<Vec<_>>();
+            if rest == ["else", "{"] {
+                let Block::If(condition, None) = block.kind else {
+                    return Err(error(number, words[1].0, "'else' without a matching 'if'".to_string()));
+                };
+                open.push(OpenBlock { line: number, column, kind: Block::If(condition, Some(block.body)), body: Vec::new() });
+                continue;
+            }
+            if let Some(&(column, word)) = words.get(1) {
+                return Err(error(number, column, format!("Unexpected '{}'", word)));
+            }
+
+            let step = match block.kind {
+                Block::Loop(count) => MacroStep::Loop(count, block.body),
+                Block::While(condition) => MacroStep::While(condition, block.body),
+                Block::If(condition, None) => MacroStep::If(condition, block.body, Vec::new()),
+                Block::If(condition, Some(then)) => MacroStep::If(condition, then, block.body),
+                Block::Sub(name) => MacroStep::Sub(name, block.body),
+            };
+            match open.last_mut() {
+                Some(parent) => parent.body.push(step),
+                None => steps.push(step),
+            }
+            continue;
+        }
+
+        if matches!(command.as_str(), "loop" | "repeat" | "while" | "if" | "sub") {
+            let brace = match words.last() {
+                Some(&(brace, "{")) if words.len() > 1 => brace,
+                _ => return Err(error(number, line.trim_end().len(), "Expected '{' at the end of the line".to_string())),
+            };
+            let head = &words[..words.len() - 1];
+            if command == "loop" || command == "repeat" || command == "sub" {
+                if let Some(&(column, word)) = head.get(2) {
+                    return Err(error(number, column, format!("Unexpected '{}'", word)));
+                }
+                if head.len() < 2 {
+                    let what = if command == "sub" { "a name" } else { "a loop count" };
+                    return Err(error(number, brace, format!("Expected {}", what)));
+                }
+            }
+
+            let kind = match command.as_str() {
+                "while" => Block::While(parse_condition(number, line, &head[1..], brace)?),
+                "if" => Block::If(parse_condition(number, line, &head[1..], brace)?, None),
+                "sub" => {
+                    let name = name_at(1)?;
+                    if !open.is_empty() {
+                        return Err(error(number, column, "Subs can only be defined at the top level".to_string()));
+                    }
+                    if steps.iter().any(|step| matches!(step, MacroStep::Sub(defined, _) if *defined == name)) {
+                        return Err(error(number, head[1].0, format!("Sub '{}' is already defined", name)));
+                    }
+                    Block::Sub(name)
+                }
+                _ => {
+                    let count = value_at(1)?;
+                    if matches!(count, Value::Number(count) if count < 1) {
+                        return Err(error(number, head[1].0, "Loop count must be at least 1".to_string()));
+                    }
+                    Block::Loop(count)
+                }
+            };
+            open.push(OpenBlock { line: number, column, kind, body: Vec::new() });
+            continue;
+        }
+
+        let (step, used) = match command.as_str() {
+            "move" => (MacroStep::Move(number_at(1, "x")?, number_at(2, "y")?), 3),
+            "click" => {
+                let button = button_at(1)?;
+                match words.get(2) {
+                    Some(&(column, word)) if word.starts_with('x') => {
+                        let clicks = word[1..].parse().ok().filter(|clicks| *clicks > 0);
+                        let clicks = clicks.ok_or_else(|| error(number, column, format!("Expected a count like x2, found '{}'", word)))?;
+                        (MacroStep::Click(button, clicks), 3)
+                    }
+                    _ => (MacroStep::Click(button, 1), 2),
+                }
+            }
+            "down" => (MacroStep::Press(button_at(1)?), 2),
+            "up" => (MacroStep::Release(button_at(1)?), 2),
+            "scroll" => {
+                let (column, word) = argument(1, "a direction")?;
+                let direction = parse_direction(word).ok_or_else(|| error(number, column, format!("Unknown direction '{}'", word)))?;
+                match words.get(2) {
+                    Some(_) => (MacroStep::Scroll(direction, number_at(2, "a number of notches")?.max(1) as u32), 3),
+                    None => (MacroStep::Scroll(direction, 1), 2),
+                }
+            }
+            "key" => {
+                let (column, word) = argument(1, "a key combo")?;
+                let keys = parse_key_combo(word).map_err(|message| error(number, column, message))?;
+                (MacroStep::Keys(keys), 2)
+            }
+            "keydown" => (MacroStep::KeyDown(key_at(1)?), 2),
+            "keyup" => (MacroStep::KeyUp(key_at(1)?), 2),
+            "wait" => {
+                let (column, word) = argument(1, "a duration")?;
+                let ms = parse_duration(word).ok_or_else(|| error(number, column, format!("Expected a duration like 150ms, found '{}'", word)))?;
+                (MacroStep::Wait(ms), 2)
+            }
+            "drag" => {
+                let from = (number_at(1, "start x")?, number_at(2, "start y")?);
+                let to = (number_at(3, "end x")?, number_at(4, "end y")?);
+                match words.get(5) {
+                    Some(_) => (MacroStep::Drag(from, to, button_at(5)?), 6),
+                    None => (MacroStep::Drag(from, to, MouseButton::Left), 5),
+                }
+            }
+            "set" => (MacroStep::Set(name_at(1)?, value_at(2)?), 3),
+            "add" => (MacroStep::Add(name_at(1)?, value_at(2)?), 3),
+            "call" => {
+                let name = name_at(1)?;
+                calls.push((number, words[1].0, name.clone()));
+                (MacroStep::Call(name), 2)
+            }
+            _ => return Err(error(number, column, format!("Unknown command '{}'", command))),
+        };
+
+        if let Some(&(column, word)) = words.get(used) {
+            return Err(error(number, column, format!("Unexpected '{}'", word)));
+        }
+        match open.last_mut() {
+            Some(block) => block.body.push(step),
+            None => steps.push(step),
+        }
+    }
+
+    if let Some(block) = open.pop() {
+        return Err(error(block.line, block.column, "Block is never closed with '}'".to_string()));
+    }
+    for (number, column, name) in calls {
+        if !steps.iter().any(|step| matches!(step, MacroStep::Sub(defined, _) if *defined == name)) {
+            return Err(error(number, column, format!("No sub called '{}'", name)));
+        }
+    }
+    Ok(steps)
+}
+
+// What a block is waiting for its '}' to become
+enum Block {
+    Loop(Value),
+    While(Condition),
+    // Holds the finished 'then' body once '} else {' is seen
+    If(Condition, Option<Vec<MacroStep>>),
+    Sub(String),
+}
+
+// A block still open while parsing, with the line and column that opened it
+struct OpenBlock {
+    line: usize,
+    column: usize,
+    kind: Block,
+    body: Vec<MacroStep>,
+}
+
+// Condition keywords, which would be ambiguous as variable names
+const RESERVED: [&str; 4] = ["window", "pixel", "elapsed", "not"];
+
+fn is_name(word: &str) -> bool {
+    let mut chars = word.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !RESERVED.contains(&word.to_lowercase().as_str())
+}
+
+fn parse_value(word: &str) -> Option<Value> {
+    match word.parse() {
+        Ok(number) => Some(Value::Number(number)),
+        Err(_) if is_name(word) => Some(Value::Var(word.to_string())),
+        Err(_) => None,
+    }
+}
+
+fn parse_compare(word: &str) -> Option<Compare> {
+    Compare::ALL.into_iter().find(|compare| compare.symbol() == word)
+}
+
+// "#rrggbb"
+pub fn parse_color(word: &str) -> Option<(u8, u8, u8)> {
+    let hex = word.strip_prefix('#').filter(|hex| hex.len() == 6 && hex.is_ascii())?;
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+// The words of a condition, `end` is the column of the '{' closing it
+fn parse_condition(number: usize, line: &str, words: &[(usize, &str)], end: usize) -> Result<Condition, ParseError> {
+    let error = |column: usize, message: String| ParseError { line: number + 1, column: column + 1, message };
+    let argument = |i: usize, what: &str| words.get(i).copied().ok_or_else(|| error(end, format!("Expected {}", what)));
+    let number_at = |i: usize, what: &str| -> Result<i32, ParseError> {
+        let (column, word) = argument(i, what)?;
+        word.parse().map_err(|_| error(column, format!("Expected {}, found '{}'", what, word)))
+    };
+    let compare_at = |i: usize| -> Result<Compare, ParseError> {
+        let (column, word) = argument(i, "a comparison")?;
+        parse_compare(word).ok_or_else(|| error(column, format!("Expected <, <=, >, >=, == or !=, found '{}'", word)))
+    };
+
+    let Some(&(column, keyword)) = words.first() else {
+        return Err(error(end, "Expected a condition".to_string()));
+    };
+    let (condition, used) = match keyword.to_lowercase().as_str() {
+        "not" => return Ok(Condition::Not(Box::new(parse_condition(number, line, &words[1..], end)?))),
+        // Everything up to the '{', so titles can have spaces
+        "window" => {
+            let (from, _) = argument(1, "a window title")?;
+            return Ok(Condition::Window(line[from..end].trim_end().to_string()));
+        }
+        "pixel" => {
+            let (x, y) = (number_at(1, "x")?, number_at(2, "y")?);
+            let (column, word) = argument(3, "a colour")?;
+            let color = parse_color(word).ok_or_else(|| error(column, format!("Expected a colour like #ff8800, found '{}'", word)))?;
+            match words.get(4) {
+                Some(&(column, word)) => {
+                    let tolerance = word.parse().map_err(|_| error(column, format!("Expected a tolerance from 0 to 255, found '{}'", word)))?;
+                    (Condition::Pixel(x, y, color, tolerance), 5)
+                }
+                None => (Condition::Pixel(x, y, color, 0), 4),
+            }
+        }
+        "elapsed" => {
+            let compare = compare_at(1)?;
+            let (column, word) = argument(2, "a duration")?;
+            let ms = parse_duration(word).ok_or_else(|| error(column, format!("Expected a duration like 150ms, found '{}'", word)))?;
+            (Condition::Elapsed(compare, ms), 3)
+        }
+        _ if is_name(keyword) => {
+            let compare = compare_at(1)?;
+            let (column, word) = argument(2, "a number or variable")?;
+            let value = parse_value(word).ok_or_else(|| error(column, format!("Expected a number or variable, found '{}'", word)))?;
+            (Condition::Var(keyword.to_string(), compare, value), 3)
+        }
+        _ => return Err(error(column, format!("Unknown condition '{}'", keyword))),
+    };
+
+    if let Some(&(column, word)) = words.get(used) {
+        return Err(error(column, format!("Unexpected '{}'", word)));
+    }
+    Ok(condition)
+}
@@ -1,7 +1,12 @@
 #![windows_subsystem = "windows"]
 
 mod clicker;
+mod editor;
 mod gui;
+mod input;
+mod macros;
+mod script;
+mod xdotool;
 
 use eframe::egui;
 use gui::AutoClickerApp;
@@ -0,0 +1,286 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use mlua::{Function, HookTriggers, Lua, LuaOptions, MultiValue, StdLib, Value, Variadic};
+
+use crate::clicker::AutoClicker;
+use crate::input::{get_active_window_title, move_pointer, parse_key_combo, pixel_color, send_keys, MouseButton};
+use crate::macros::parse_button;
+
+// What Script mode starts with, clicks left on every tick like Left mode
+pub const EXAMPLE_SCRIPT: &str = "-- Called at the clicker's rate while it runs
+function on_tick()
+  click(\"left\")
+end
+";
+
+// Wall time one hook call may take, not counting its sleeps
+pub const TIME_LIMIT: Duration = Duration::from_secs(2);
+// Lua instructions one hook call may run
+pub const INSTRUCTION_LIMIT: u64 = 50_000_000;
+const MEMORY_LIMIT: usize = 16 * 1024 * 1024;
+// The limits are checked every this many instructions
+const CHECK_INTERVAL: u32 = 10_000;
+
+// Replaces the base functions that could run code the limits can't stop. pcall and xpcall
+// raise a cut off call again, and an xpcall handler doesn't run for one, as Lua runs it with
+// hooks off. Finalizers also run with hooks off, so __gc isn't allowed.
+const GUARDS: &str = r#"
+local pcall, xpcall, setmetatable, check, tripped = ...
+local type, rawget, error = type, rawget, error
+
+local function guarded_pcall(...)
+  return check(pcall(...))
+end
+
+local function guarded_xpcall(f, handler, ...)
+  return check(xpcall(f, function(message)
+    if tripped() then
+      return message
+    end
+    return handler(message)
+  end, ...))
+end
+
+local function guarded_setmetatable(t, mt)
+  if type(mt) == "table" and rawget(mt, "__gc") ~= nil then
+    error("__gc metamethods are not allowed", 2)
+  end
+  return setmetatable(t, mt)
+end
+
+return guarded_pcall, guarded_xpcall, guarded_setmetatable
+"#;
+
+// How much of its limits the current hook call has used
+struct Budget {
+    started: Cell<Instant>,
+    slept: Cell<Duration>,
+    instructions: Cell<u64>,
+    // Why the call was cut off. Once set every check fails again, so pcall can't catch it
+    tripped: RefCell<Option<String>>,
+}
+
+impl Budget {
+    fn reset(&self) {
+        self.started.set(Instant::now());
+        self.slept.set(Duration::ZERO);
+        self.instructions.set(0);
+        *self.tripped.borrow_mut() = None;
+    }
+
+    fn trip(&self, reason: String) -> mlua::Error {
+        *self.tripped.borrow_mut() = Some(reason.clone());
+        mlua::Error::runtime(reason)
+    }
+
+    // The error to raise again if the call has been cut off
+    fn tripped(&self) -> mlua::Result<()> {
+        match self.tripped.borrow().as_ref() {
+            Some(reason) => Err(mlua::Error::runtime(reason.clone())),
+            None => Ok(()),
+        }
+    }
+
+    fn check(&self, clicker: &AutoClicker) -> mlua::Result<()> {
+        self.tripped()?;
+        self.instructions.set(self.instructions.get() + CHECK_INTERVAL as u64);
+        if !clicker.is_running() {
+            return Err(self.trip("stopped".to_string()));
+        }
+        if self.instructions.get() > INSTRUCTION_LIMIT {
+            return Err(self.trip(format!("ran over {} instructions in one call", INSTRUCTION_LIMIT)));
+        }
+        if self.started.get().elapsed().saturating_sub(self.slept.get()) > TIME_LIMIT {
+            return Err(self.trip(format!("ran over {}s in one call", TIME_LIMIT.as_secs())));
+        }
+        Ok(())
+    }
+}
+
+// The first line of a Lua error, without the traceback
+fn error_text(error: mlua::Error) -> String {
+    error.to_string().lines().next().unwrap_or_default().to_string()
+}
+
+fn display(value: &Value) -> String {
+    match value {
+        Value::Nil => "nil".to_string(),
+        Value::Boolean(value) => value.to_string(),
+        Value::Integer(value) => value.to_string(),
+        Value::Number(value) => value.to_string(),
+        Value::String(value) => value.to_string_lossy().into_owned(),
+        value => value.type_name().to_string(),
+    }
+}
+
+// A user script in its own sandboxed Lua state. Only the table, string, math and utf8 libraries
+// are there, so it can't reach files, the OS or other code, and every hook call runs under an
+// instruction, time and memory limit. Lives on the mode scheduler thread.
+pub struct Script {
+    lua: Lua,
+    budget: Rc<Budget>,
+    started: bool,
+    window: String,
+}
+
+impl Script {
+    // Runs the script's top level once, which defines its hooks
+    pub fn load(clicker: &AutoClicker, source: &str) -> Result<Script, String> {
+        let libs = StdLib::TABLE | StdLib::STRING | StdLib::MATH | StdLib::UTF8;
+        let lua = Lua::new_with(libs, LuaOptions::default()).map_err(error_text)?;
+        lua.set_memory_limit(MEMORY_LIMIT).map_err(error_text)?;
+
+        let budget = Rc::new(Budget {
+            started: Cell::new(Instant::now()),
+            slept: Cell::new(Duration::ZERO),
+            instructions: Cell::new(0),
+            tripped: RefCell::new(None),
+        });
+        let hook_budget = budget.clone();
+        let hook_clicker = clicker.clone();
+        lua.set_hook(HookTriggers::new().every_nth_instruction(CHECK_INTERVAL), move |_, _| {
+            hook_budget.check(&hook_clicker)
+        });
+
+        Self::add_api(&lua, clicker, &budget).map_err(error_text)?;
+
+        budget.reset();
+        lua.load(source).set_name("script").exec().map_err(error_text)?;
+        Ok(Script { lua, budget, started: false, window: String::new() })
+    }
+
+    fn add_api(lua: &Lua, clicker: &AutoClicker, budget: &Rc<Budget>) -> mlua::Result<()> {
+        let globals = lua.globals();
+
+        // Base library functions that load other code
+        for name in ["dofile", "loadfile", "load", "require"] {
+            globals.set(name, Value::Nil)?;
+        }
+
+        let check_budget = budget.clone();
+        let check = lua.create_function(move |_, results: MultiValue| {
+            check_budget.tripped()?;
+            Ok(results)
+        })?;
+        let tripped_budget = budget.clone();
+        let tripped = lua.create_function(move |_, ()| Ok(tripped_budget.tripped().is_err()))?;
+        let originals: (Function, Function, Function) =
+            (globals.get("pcall")?, globals.get("xpcall")?, globals.get("setmetatable")?);
+        let (pcall, xpcall, setmetatable): (Function, Function, Function) =
+            lua.load(GUARDS).set_name("guards").call((originals.0, originals.1, originals.2, check, tripped))?;
+        globals.set("pcall", pcall)?;
+        globals.set("xpcall", xpcall)?;
+        globals.set("setmetatable", setmetatable)?;
+
+        let api_clicker = clicker.clone();
+        globals.set(
+            "click",
+            lua.create_function(move |_, (button, clicks): (Option<String>, Option<u32>)| {
+                let button = match button {
+                    Some(name) => parse_button(&name).ok_or_else(|| mlua::Error::runtime(format!("unknown button '{}'", name)))?,
+                    None => MouseButton::Left,
+                };
+                api_clicker.burst(button, clicks.unwrap_or(1).clamp(1, 10));
+                Ok(())
+            })?,
+        )?;
+
+        globals.set(
+            "move",
+            lua.create_function(|_, (x, y): (i32, i32)| {
+                move_pointer(x, y);
+                Ok(())
+            })?,
+        )?;
+
+        // Sleeps don't count against the time limit, and end early when the clicker stops
+        let api_clicker = clicker.clone();
+        let api_budget = budget.clone();
+        globals.set(
+            "sleep",
+            lua.create_function(move |_, ms: u64| {
+                let started = Instant::now();
+                let duration = Duration::from_millis(ms);
+                while api_clicker.is_running() && started.elapsed() < duration {
+                    thread::sleep((duration - started.elapsed()).min(Duration::from_millis(10)));
+                }
+                api_budget.slept.set(api_budget.slept.get() + started.elapsed());
+                match api_clicker.is_running() {
+                    true => Ok(()),
+                    false => Err(api_budget.trip("stopped".to_string())),
+                }
+            })?,
+        )?;
+
+        // r, g, b or three nils if the screen can't be read there
+        globals.set(
+            "pixel",
+            lua.create_function(|_, (x, y): (i32, i32)| {
+                Ok(match pixel_color(x, y) {
+                    Some((r, g, b)) => (Some(r), Some(g), Some(b)),
+                    None => (None, None, None),
+                })
+            })?,
+        )?;
+
+        globals.set("focused_window", lua.create_function(|_, ()| Ok(get_active_window_title()))?)?;
+
+        globals.set(
+            "key",
+            lua.create_function(|_, combo: String| {
+                let keys = parse_key_combo(&combo).map_err(mlua::Error::runtime)?;
+                send_keys(&keys);
+                Ok(())
+            })?,
+        )?;
+
+        // Printed lines go to the script log in the GUI
+        let api_clicker = clicker.clone();
+        globals.set(
+            "print",
+            lua.create_function(move |_, values: Variadic<Value>| {
+                let line = values.iter().map(display).collect::<Vec<_>>().join("\t");
+                api_clicker.log_script(&line);
+                Ok(())
+            })?,
+        )?;
+
+        Ok(())
+    }
+
+    // Calls a hook if the script defines it
+    fn call<A: for<'lua> mlua::IntoLuaMulti<'lua>>(&self, name: &str, args: A) -> Result<(), String> {
+        let hook: Option<mlua::Function> = self.lua.globals().get(name).map_err(error_text)?;
+        let Some(hook) = hook else {
+            return Ok(());
+        };
+        self.budget.reset();
+        hook.call::<_, ()>(args).map_err(|error| format!("{}: {}", name, error_text(error)))
+    }
+
+    // One scheduler tick: on_start after each activation, on_window_change when the focused
+    // window changed since the last tick, then on_tick
+    pub fn tick(&mut self) -> Result<(), String> {
+        if !self.started {
+            self.started = true;
+            self.window = get_active_window_title();
+            self.call("on_start", ())?;
+        }
+
+        let window = get_active_window_title();
+        if window != self.window {
+            self.window = window.clone();
+            self.call("on_window_change", window)?;
+        }
+
+        self.call("on_tick", ())
+    }
+
+    // The clicker was deactivated, on_start runs again next time
+    pub fn reset(&mut self) {
+        self.started = false;
+    }
+}
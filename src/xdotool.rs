@@ -0,0 +1,375 @@
+use std::path::Path;
+
+use crate::clicker::ClickPoint;
+use crate::input::{Key, MouseButton, ScrollDirection};
+use crate::macros::{words, Condition, MacroStep, ParseError, Value};
+
+// Shell scripts that drive xdotool, so automations can be shared with people who don't run
+// the GUI. Exports are bash because conditions and counters need its arithmetic.
+
+const SHEBANG: &str = "#!/usr/bin/env bash";
+// Start time for elapsed conditions, only written when the macro has one
+const STARTED: &str = "started=$(date +%s%3N)";
+
+fn button_number(button: MouseButton) -> u32 {
+    match button {
+        MouseButton::Left => 1,
+        MouseButton::Middle => 2,
+        MouseButton::Right => 3,
+        MouseButton::X1 => 8,
+        MouseButton::X2 => 9,
+    }
+}
+
+// xdotool clicks the wheel as buttons 4 to 7
+fn scroll_number(direction: ScrollDirection) -> u32 {
+    match direction {
+        ScrollDirection::Up => 4,
+        ScrollDirection::Down => 5,
+        ScrollDirection::Left => 6,
+        ScrollDirection::Right => 7,
+    }
+}
+
+fn seconds(ms: u32) -> String {
+    format!("{}.{:03}", ms / 1000, ms % 1000)
+}
+
+fn key_combo(keys: &[Key]) -> String {
+    keys.iter().map(|key| key.xdo_name()).collect::<Vec<_>>().join("+")
+}
+
+// Single quotes for the shell, closing and reopening them around any quote in the text
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+// Macro variables get a prefix so they can't clash with the shell's own
+fn value(value: &Value) -> String {
+    match value {
+        Value::Number(number) => number.to_string(),
+        Value::Var(name) => format!("v_{}", name),
+    }
+}
+
+fn condition(condition: &Condition) -> Result<String, String> {
+    Ok(match condition {
+        Condition::Window(title) => {
+            format!("xdotool getactivewindow getwindowname 2>/dev/null | grep -qiF -- {}", quote(title))
+        }
+        Condition::Pixel(..) => return Err("Pixel conditions have no xdotool equivalent".to_string()),
+        Condition::Elapsed(compare, ms) => format!("(( $(date +%s%3N) - started {} {} ))", compare.symbol(), ms),
+        Condition::Var(name, compare, right) => format!("(( v_{} {} {} ))", name, compare.symbol(), value(right)),
+        Condition::Not(inner) => format!("! {}", self::condition(inner)?),
+    })
+}
+
+fn uses_elapsed(steps: &[MacroStep]) -> bool {
+    fn test(condition: &Condition) -> bool {
+        match condition {
+            Condition::Elapsed(..) => true,
+            Condition::Not(inner) => test(inner),
+            _ => false,
+        }
+    }
+    steps.iter().any(|step| match step {
+        MacroStep::Loop(_, body) | MacroStep::Sub(_, body) => uses_elapsed(body),
+        MacroStep::While(condition, body) => test(condition) || uses_elapsed(body),
+        MacroStep::If(condition, then, otherwise) => test(condition) || uses_elapsed(then) || uses_elapsed(otherwise),
+        _ => false,
+    })
+}
+
+// `loops` numbers the loop counters so nested loops and subs never share one, inside a sub
+// they are also local so a recursive call can't reset its caller's
+fn write_steps(
+    script: &mut String,
+    steps: &[MacroStep],
+    depth: usize,
+    loops: &mut usize,
+    in_sub: bool,
+) -> Result<(), String> {
+    let line = |script: &mut String, text: String| {
+        script.push_str(&"  ".repeat(depth));
+        script.push_str(&text);
+        script.push('\n');
+    };
+
+    // Bash doesn't allow empty blocks, ':' does nothing
+    if steps.iter().all(|step| matches!(step, MacroStep::Sub(..))) {
+        line(script, ":".to_string());
+        return Ok(());
+    }
+
+    for step in steps {
+        match step {
+            MacroStep::Move(x, y) => line(script, format!("xdotool mousemove {} {}", x, y)),
+            MacroStep::Click(button, 1) => line(script, format!("xdotool click {}", button_number(*button))),
+            MacroStep::Click(button, clicks) => {
+                line(script, format!("xdotool click --repeat {} {}", clicks, button_number(*button)))
+            }
+            MacroStep::Press(button) => line(script, format!("xdotool mousedown {}", button_number(*button))),
+            MacroStep::Release(button) => line(script, format!("xdotool mouseup {}", button_number(*button))),
+            MacroStep::Scroll(direction, notches) => {
+                line(script, format!("xdotool click --repeat {} {}", notches, scroll_number(*direction)))
+            }
+            MacroStep::Keys(keys) => line(script, format!("xdotool key {}", key_combo(keys))),
+            MacroStep::KeyDown(key) => line(script, format!("xdotool keydown {}", key.xdo_name())),
+            MacroStep::KeyUp(key) => line(script, format!("xdotool keyup {}", key.xdo_name())),
+            MacroStep::Wait(ms) => line(script, format!("sleep {}", seconds(*ms))),
+            MacroStep::Drag(from, to, button) => {
+                let button = button_number(*button);
+                line(
+                    script,
+                    format!(
+                        "xdotool mousemove {} {} mousedown {} sleep 0.05 mousemove {} {} sleep 0.05 mouseup {}",
+                        from.0, from.1, button, to.0, to.1, button
+                    ),
+                );
+            }
+            MacroStep::Loop(count, body) => {
+                // The count is read once, like playback does
+                *loops += 1;
+                let n = *loops;
+                if in_sub {
+                    line(script, format!("local i{0} n{0}", n));
+                }
+                line(script, format!("for (( i{0} = 0, n{0} = {1}; i{0} < n{0}; i{0}++ )); do", n, value(count)));
+                write_steps(script, body, depth + 1, loops, in_sub)?;
+                line(script, "done".to_string());
+            }
+            MacroStep::While(test, body) => {
+                line(script, format!("while {}; do", condition(test)?));
+                write_steps(script, body, depth + 1, loops, in_sub)?;
+                line(script, "done".to_string());
+            }
+            MacroStep::If(test, then, otherwise) => {
+                line(script, format!("if {}; then", condition(test)?));
+                write_steps(script, then, depth + 1, loops, in_sub)?;
+                if !otherwise.is_empty() {
+                    line(script, "else".to_string());
+                    write_steps(script, otherwise, depth + 1, loops, in_sub)?;
+                }
+                line(script, "fi".to_string());
+            }
+            MacroStep::Set(name, to) => line(script, format!("v_{}=$(( {} ))", name, value(to))),
+            MacroStep::Add(name, by) => line(script, format!("v_{0}=$(( v_{0} + {1} ))", name, value(by))),
+            // Written up front as functions
+            MacroStep::Sub(..) => {}
+            MacroStep::Call(name) => line(script, format!("sub_{}", name)),
+        }
+    }
+    Ok(())
+}
+
+// The macro as a bash script, fails for steps xdotool can't do
+pub fn export_macro(steps: &[MacroStep]) -> Result<String, String> {
+    let mut script = format!("{}\n# Macro exported from rust_clicker, needs xdotool\n\n", SHEBANG);
+    if uses_elapsed(steps) {
+        script.push_str(&format!("{}\n\n", STARTED));
+    }
+    let mut loops = 0;
+
+    // Subs become functions, defined before anything can call them
+    for step in steps {
+        if let MacroStep::Sub(name, body) = step {
+            script.push_str(&format!("sub_{}() {{\n", name));
+            write_steps(&mut script, body, 1, &mut loops, true)?;
+            script.push_str("}\n\n");
+        }
+    }
+
+    write_steps(&mut script, steps, 0, &mut loops, false)?;
+    Ok(script)
+}
+
+// The click list as a bash script, `loops` of 0 repeats forever. With an anchor window the
+// points are offset by its position, looked up again on every loop in case it moved.
+pub fn export_click_list(points: &[ClickPoint], loops: u32, anchor: &str, modifiers: &[Key]) -> String {
+    let mut script = format!("{}\n# Click list exported from rust_clicker, needs xdotool\n\n", SHEBANG);
+
+    if loops == 0 {
+        script.push_str("while true; do\n");
+    } else {
+        script.push_str(&format!("for (( loop = 0; loop < {}; loop++ )); do\n", loops));
+    }
+
+    let anchor = anchor.trim();
+    let position = |x: i32, y: i32| match anchor.is_empty() {
+        true => format!("{} {}", x, y),
+        false => format!("$(( X + {} )) $(( Y + {} ))", x, y),
+    };
+    if !anchor.is_empty() {
+        script.push_str(&format!(
+            "  geometry=$(xdotool search --onlyvisible --name --class {} getwindowgeometry --shell %1) || exit 1\n  eval \"$geometry\"\n",
+            quote(anchor)
+        ));
+    }
+
+    let (hold, release) = match modifiers.is_empty() {
+        true => (String::new(), String::new()),
+        false => {
+            let names = modifiers.iter().map(|key| key.xdo_name()).collect::<Vec<_>>().join(" ");
+            (format!("keydown {} ", names), format!(" keyup {}", names))
+        }
+    };
+
+    for point in points {
+        let button = button_number(point.button);
+        let action = match point.drag_to {
+            Some((to_x, to_y)) => format!(
+                "mousemove {} mousedown {} sleep 0.05 mousemove {} sleep 0.05 mouseup {}",
+                position(point.x, point.y),
+                button,
+                position(to_x, to_y),
+                button
+            ),
+            None if point.clicks > 1 => {
+                format!("mousemove {} click --repeat {} {}", position(point.x, point.y), point.clicks, button)
+            }
+            None => format!("mousemove {} click {}", position(point.x, point.y), button),
+        };
+        script.push_str(&format!("  xdotool {}{}{}\n", hold, action, release));
+        script.push_str(&format!("  sleep {}\n", seconds(point.delay_ms)));
+    }
+
+    script.push_str("done\n");
+    script
+}
+
+// Writes a script, made executable where that means something
+pub fn write_script(path: &Path, script: &str) -> std::io::Result<()> {
+    std::fs::write(path, script)?;
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(())
+}
+
+// xdotool also spells keys as X keysyms, with sides and "Control"
+fn import_key(name: &str) -> Option<Key> {
+    let name = name.strip_suffix("_L").or_else(|| name.strip_suffix("_R")).unwrap_or(name);
+    match name.to_lowercase().as_str() {
+        "control" => Key::from_name("ctrl"),
+        "meta" => Key::from_name("super"),
+        _ => Key::from_name(name),
+    }
+}
+
+const COMMANDS: [&str; 8] = ["mousemove", "click", "mousedown", "mouseup", "key", "keydown", "keyup", "sleep"];
+
+// Reads the part of xdotool that maps onto macro steps: mousemove, click, mousedown, mouseup,
+// key, keydown, keyup and sleep, chained or one per line, plus the shell's own sleep. Anything
+// else, like loops or window commands, is an error pointing at it.
+pub fn import_macro(text: &str) -> Result<Vec<MacroStep>, ParseError> {
+    let mut steps = Vec::new();
+
+    for (number, line) in text.lines().enumerate() {
+        let error = |column: usize, message: String| ParseError { line: number + 1, column: column + 1, message };
+        let words = words(line);
+        let Some(&(column, first)) = words.first() else {
+            continue;
+        };
+        // Comments, and the start time line exports write
+        if first.starts_with('#') || line.trim() == STARTED {
+            continue;
+        }
+
+        let end = line.trim_end().len();
+        let number_at = |i: usize, what: &str| -> Result<f64, ParseError> {
+            let (column, word) = words.get(i).copied().ok_or_else(|| error(end, format!("Expected {}", what)))?;
+            word.parse().map_err(|_| error(column, format!("Expected {}, found '{}'", what, word)))
+        };
+
+        if first == "sleep" {
+            steps.push(MacroStep::Wait((number_at(1, "seconds")?.max(0.0) * 1000.0).round() as u32));
+            if let Some(&(column, word)) = words.get(2) {
+                return Err(error(column, format!("Unexpected '{}'", word)));
+            }
+            continue;
+        }
+        if first != "xdotool" {
+            return Err(error(column, format!("Only xdotool and sleep lines can be imported, found '{}'", first)));
+        }
+
+        let mut i = 1;
+        if words.len() == 1 {
+            return Err(error(end, "Expected an xdotool command".to_string()));
+        }
+        while let Some(&(column, command)) = words.get(i) {
+            i += 1;
+
+            // Options come first, only the ones that change the steps are kept
+            let mut repeat = 1;
+            while let Some(&(column, option)) = words.get(i).filter(|(_, word)| word.starts_with("--")) {
+                i += 1;
+                match option {
+                    "--repeat" => {
+                        repeat = number_at(i, "a repeat count")?.max(1.0) as u32;
+                        i += 1;
+                    }
+                    "--delay" => i += 1,
+                    "--sync" | "--clearmodifiers" => {}
+                    _ => return Err(error(column, format!("Unsupported option '{}'", option))),
+                }
+            }
+
+            match command {
+                "mousemove" => {
+                    steps.push(MacroStep::Move(number_at(i, "x")? as i32, number_at(i + 1, "y")? as i32));
+                    i += 2;
+                }
+                "click" | "mousedown" | "mouseup" => {
+                    let (column, word) = words.get(i).copied().ok_or_else(|| error(end, "Expected a button".to_string()))?;
+                    i += 1;
+                    let button = match word {
+                        "1" => Ok(MouseButton::Left),
+                        "2" => Ok(MouseButton::Middle),
+                        "3" => Ok(MouseButton::Right),
+                        "8" => Ok(MouseButton::X1),
+                        "9" => Ok(MouseButton::X2),
+                        "4" => Err(ScrollDirection::Up),
+                        "5" => Err(ScrollDirection::Down),
+                        "6" => Err(ScrollDirection::Left),
+                        "7" => Err(ScrollDirection::Right),
+                        _ => return Err(error(column, format!("Unknown button '{}'", word))),
+                    };
+                    steps.push(match (command, button) {
+                        ("click", Ok(button)) => MacroStep::Click(button, repeat),
+                        ("click", Err(direction)) => MacroStep::Scroll(direction, repeat),
+                        ("mousedown", Ok(button)) => MacroStep::Press(button),
+                        ("mouseup", Ok(button)) => MacroStep::Release(button),
+                        _ => return Err(error(column, "The wheel can only be clicked".to_string())),
+                    });
+                }
+                "key" | "keydown" | "keyup" => {
+                    // Every word up to the next command is a combo
+                    let start = i;
+                    while let Some(&(column, combo)) = words.get(i).filter(|(_, word)| !COMMANDS.contains(word)) {
+                        i += 1;
+                        let keys = combo
+                            .split('+')
+                            .map(|name| import_key(name).ok_or_else(|| error(column, format!("Unknown key '{}'", name))))
+                            .collect::<Result<Vec<_>, _>>()?;
+                        match command {
+                            "key" => steps.extend((0..repeat).map(|_| MacroStep::Keys(keys.clone()))),
+                            "keydown" => steps.extend(keys.into_iter().map(MacroStep::KeyDown)),
+                            _ => steps.extend(keys.into_iter().rev().map(MacroStep::KeyUp)),
+                        }
+                    }
+                    if i == start {
+                        return Err(error(end, "Expected a key".to_string()));
+                    }
+                }
+                "sleep" => {
+                    steps.push(MacroStep::Wait((number_at(i, "seconds")?.max(0.0) * 1000.0).round() as u32));
+                    i += 1;
+                }
+                _ => return Err(error(column, format!("Unsupported xdotool command '{}'", command))),
+            }
+        }
+    }
+    Ok(steps)
+}